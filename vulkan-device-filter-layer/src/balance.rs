@@ -0,0 +1,54 @@
+use std::{
+    env,
+    fs,
+    io::{
+        self,
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    os::unix::io::AsRawFd,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+const COUNTER_FILE: &'static str = "vulkan-device-filter-balance";
+
+/// Directory holding the round-robin counter: `$XDG_RUNTIME_DIR`, or the temporary directory if
+/// that isn't set
+pub fn runtime_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+}
+
+/// Takes the next ticket from the round-robin counter in `dir`, shared between all processes
+/// using the same directory. The counter file is locked while it's being updated.
+pub fn next_ticket<P: AsRef<Path>>(dir: P) -> io::Result<u64> {
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(dir.as_ref().join(COUNTER_FILE))?;
+    // The lock is released when the file is closed
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let ticket = contents.trim().parse().unwrap_or(0u64);
+    file.seek(SeekFrom::Start(0))?;
+    file.set_len(0)?;
+    write!(file, "{}", ticket.wrapping_add(1))?;
+    Ok(ticket)
+}
+
+/// 64-bit FNV-1a hash. Unlike `std`'s hashers, this is guaranteed to give the same result in
+/// every process and every build, so it can be used to place jobs.
+pub fn stable_hash(s: &str) -> u64 {
+    s.bytes()
+        .fold(0xcbf29ce484222325, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
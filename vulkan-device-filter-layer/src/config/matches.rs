@@ -1,6 +1,7 @@
-use vulkan_sys as sys;
-
-use super::MatchRule;
+use super::{
+    Filter,
+    MatchRule,
+};
 
 use log::{
     debug,
@@ -9,7 +10,10 @@ use log::{
 use std::{
     env,
 };
-use crate::vk;
+use crate::{
+    vk,
+    VulkanHandle,
+};
 
 trait ResultExt<T, E> {
     fn value_side_effect<F>(self, f: F) -> Self
@@ -61,12 +65,36 @@ impl<A> OptionExt<A> for Option<A> {
     }
 }
 
+/// Looks up a value in the snapshot taken when `instance` was created, or works it out now with
+/// `live` if that wasn't recorded. Before there's an instance, all we can do is look now.
+fn recorded_or_live<H, T, R, L>(instance: H, recorded: R, live: L) -> T
+where
+    H: VulkanHandle,
+    R: FnOnce(&crate::dispatches::Snapshot) -> Option<T>,
+    L: FnOnce() -> T,
+{
+    let recorded = crate::dispatches::snapshots().read().unwrap()
+        .get(&instance.vulkan_handle_key())
+        .and_then(recorded);
+    recorded.unwrap_or_else(live)
+}
+
+fn current_dir() -> Option<std::path::PathBuf> {
+    env::current_dir()
+        .err_side_effect(|e| warn!("Could not get current directory: {:?}", e))
+        .ok()
+}
+
+/// Matches against the state recorded for an instance.
+///
+/// Any handle sharing the instance's dispatch key (the instance itself, or one of its physical
+/// devices) may be used to look up that state.
 pub trait InstanceMatch {
-    fn is_match(&self, instance: sys::VkInstance) -> bool;
+    fn is_match<H: VulkanHandle + Copy>(&self, instance: H) -> bool;
 }
 
 impl InstanceMatch for MatchRule {
-    fn is_match(&self, instance: sys::VkInstance) -> bool {
+    fn is_match<H: VulkanHandle + Copy>(&self, instance: H) -> bool {
         use libc_regex_sys::{
             RegcompFlags,
             RegcompFlagsBuilder,
@@ -92,10 +120,7 @@ impl InstanceMatch for MatchRule {
                     .unwrap_or(false)
             },
             MatchRule::AppInfo { name, engine, app_version, engine_version, api_version } => {
-                use crate::{
-                    dispatches,
-                    VulkanHandle,
-                };
+                use crate::dispatches;
                 let application_infos = dispatches::application_infos().read().unwrap();
                 if let Some(application_info) = application_infos.get(&instance.vulkan_handle_key()) {
                     debug!("application info: {:?}", application_info);
@@ -117,18 +142,18 @@ impl InstanceMatch for MatchRule {
                             return false;
                         }
                     }
-                    if let Some(app_version) = app_version.as_ref().and_then(maybe_pattern) {
+                    if let Some(app_version) = app_version.as_ref().and_then(maybe_version_pattern) {
                         if version_match_excludes(&app_version, application_info.application_version.as_opt()) {
                             return false;
                         }
                     }
-                    if let Some(engine_version) = engine_version.as_ref().and_then(maybe_pattern) {
+                    if let Some(engine_version) = engine_version.as_ref().and_then(maybe_version_pattern) {
                         if version_match_excludes(&engine_version, application_info.engine_version.as_opt()) {
                             return false;
                         }
                     }
-                    if let Some(api_version) = api_version.as_ref().and_then(maybe_pattern) {
-                        if version_match_excludes(&api_version, application_info.application_version.as_opt()) {
+                    if let Some(api_version) = api_version.as_ref().and_then(maybe_version_pattern) {
+                        if version_match_excludes(&api_version, application_info.api_version.as_opt()) {
                             return false;
                         }
                     }
@@ -146,9 +171,161 @@ impl InstanceMatch for MatchRule {
                     .map(|rule| rule.is_match(instance))
                     .fold(false, |a, b| a || b)
             },
+            MatchRule::Power { source } => {
+                let current = recorded_or_live(instance,
+                                               |snapshot| snapshot.power_source,
+                                               || crate::sysfs::power_source(super::Config::global().sysfs_root()));
+                current == *source
+            },
+            MatchRule::InstanceExtensions { any, all } => {
+                use crate::dispatches;
+                dispatches::enabled_names().read().unwrap()
+                    .get(&instance.vulkan_handle_key())
+                    .map(|enabled_names| is_names_match(&enabled_names.extensions, any.as_ref(), all.as_ref()))
+                    .unwrap_or(false)
+            },
+            MatchRule::InstanceLayers { any, all } => {
+                use crate::dispatches;
+                dispatches::enabled_names().read().unwrap()
+                    .get(&instance.vulkan_handle_key())
+                    .map(|enabled_names| is_names_match(&enabled_names.layers, any.as_ref(), all.as_ref()))
+                    .unwrap_or(false)
+            },
+            MatchRule::User { name, uid } => {
+                use crate::process;
+                let real_uid = process::uid();
+                is_id_match(real_uid, process::user_name, name.as_ref(), *uid)
+            },
+            MatchRule::Group { name, gid } => {
+                use crate::process;
+                process::gids().into_iter()
+                    .any(|real_gid| is_id_match(real_gid, process::group_name, name.as_ref(), *gid))
+            },
+            MatchRule::Session { session_type } => {
+                crate::process::session_type() == *session_type
+            },
+            MatchRule::Flatpak { app_id } => {
+                crate::process::flatpak_app_id(super::Config::global().fs_root())
+                    .and_then_zip(|| maybe_pattern(app_id))
+                    .map(|(real_app_id, pattern)| pattern.is_match(&real_app_id))
+                    .unwrap_or(false)
+            },
+            MatchRule::Snap { name } => {
+                // snapd also sets SNAP_NAME, if the cgroups don't tell
+                crate::process::snap_name(super::Config::global().fs_root())
+                    .or_else(|| env::var("SNAP_NAME").ok())
+                    .and_then_zip(|| maybe_pattern(name))
+                    .map(|(real_name, pattern)| pattern.is_match(&real_name))
+                    .unwrap_or(false)
+            },
+            MatchRule::Cgroup { path } => {
+                maybe_pattern(path)
+                    .map(|pattern| {
+                        crate::process::cgroups(super::Config::global().fs_root()).iter()
+                            .any(|real_path| pattern.is_match(real_path))
+                    })
+                    .unwrap_or(false)
+            },
+            MatchRule::Ancestor { name, max_depth } => {
+                maybe_pattern(name)
+                    .map(|pattern| {
+                        let is_ancestor_match = |ancestors: &[crate::process::ProcessInfo]| {
+                            ancestors.iter()
+                                .take(max_depth.unwrap_or(usize::MAX))
+                                .any(|ancestor| {
+                                    let exe_match = ancestor.exe.as_ref()
+                                        .and_then(|exe| exe.to_str())
+                                        .map(|exe| pattern.is_match(exe))
+                                        .unwrap_or(false);
+                                    exe_match || pattern.is_match(&ancestor.comm)
+                                })
+                        };
+                        recorded_or_live(instance,
+                                         |snapshot| snapshot.ancestors.as_ref().map(|ancestors| is_ancestor_match(ancestors)),
+                                         || is_ancestor_match(&crate::process::ancestors(super::Config::global().fs_root(), *max_depth)))
+                    })
+                    .unwrap_or(false)
+            },
+            MatchRule::Host { name } => {
+                crate::process::host_name(super::Config::global().fs_root())
+                    .and_then_zip(|| maybe_pattern(name))
+                    .map(|(real_name, pattern)| pattern.is_match(&real_name))
+                    .unwrap_or(false)
+            },
+            MatchRule::PresentDevices { any, all } => {
+                // The devices can only be listed through an instance
+                recorded_or_live(instance,
+                                 |snapshot| snapshot.present_devices.as_ref().map(|names| is_patterns_match(names, any.as_ref(), all.as_ref())),
+                                 || false)
+            },
+            MatchRule::Cwd { path } => {
+                let is_cwd_match = |cwd: &std::path::Path| {
+                    cwd.to_str()
+                        .and_then_zip(|| maybe_pattern(path))
+                        .map(|(real_path, pattern)| pattern.is_match(real_path))
+                        .unwrap_or(false)
+                };
+                recorded_or_live(instance,
+                                 |snapshot| snapshot.cwd.as_ref().map(|cwd| is_cwd_match(cwd)),
+                                 || current_dir().map(|cwd| is_cwd_match(&cwd)).unwrap_or(false))
+            },
+            MatchRule::FileExists { path } => {
+                recorded_or_live(instance,
+                                 |snapshot| snapshot.existing_files.get(path).copied(),
+                                 || current_dir().map(|cwd| crate::process::file_exists(cwd, path)).unwrap_or(false))
+            },
+            MatchRule::Schedule { days, hours } => {
+                use crate::schedule;
+                let time = recorded_or_live(instance, |snapshot| snapshot.time, schedule::now);
+                schedule::is_scheduled(days.as_ref().map(Vec::as_slice), hours.as_ref().map(Vec::as_slice), time)
+            },
         }
     }
 }
+/// The compiled `hide_extensions` and `only_extensions` patterns of a filter
+pub struct ExtensionPatterns {
+    hide: Vec<libc_regex_sys::Regex>,
+    only: Option<Vec<libc_regex_sys::Regex>>,
+}
+
+impl ExtensionPatterns {
+    /// Returns `true` if the extension should be exposed to the application. Hiding takes
+    /// precedence over `only_extensions`.
+    pub fn is_visible(&self, name: &str) -> bool {
+        let matches_any = |patterns: &[libc_regex_sys::Regex]| patterns.iter().any(|pattern| pattern.is_match(name));
+        if matches_any(&self.hide) {
+            return false;
+        }
+        self.only.as_ref().map(|only| matches_any(only)).unwrap_or(true)
+    }
+}
+
+pub trait ExtensionMatch {
+    /// Compiles the extension patterns, to check any number of extensions against
+    fn extension_patterns(&self) -> ExtensionPatterns;
+
+    fn filters_extensions(&self) -> bool;
+}
+
+impl ExtensionMatch for Filter {
+    fn extension_patterns(&self) -> ExtensionPatterns {
+        let compile = |patterns: &[String]| -> Vec<libc_regex_sys::Regex> {
+            patterns.iter()
+                .filter_map(maybe_pattern)
+                .collect()
+        };
+        ExtensionPatterns {
+            hide: self.hide_extensions().map(compile).unwrap_or_default(),
+            only: self.only_extensions().map(compile),
+        }
+    }
+
+    #[inline]
+    fn filters_extensions(&self) -> bool {
+        self.hide_extensions().is_some() || self.only_extensions().is_some()
+    }
+}
+
 fn maybe_pattern<S: AsRef<str>>(s: S) -> Option<libc_regex_sys::Regex> {
     use libc_regex_sys::{
         Regex,
@@ -160,14 +337,76 @@ fn maybe_pattern<S: AsRef<str>>(s: S) -> Option<libc_regex_sys::Regex> {
         .ok()
 }
 
-fn version_match_excludes(pattern: &libc_regex_sys::Regex, version: Option<vk::VulkanSemanticVersion>) -> bool {
+/// Returns `true` if `names` contains all of `all`, and at least one of `any`
+fn is_names_match(names: &[String], any: Option<&Vec<String>>, all: Option<&Vec<String>>) -> bool {
+    let is_enabled = |name: &String| names.contains(name);
+    let has_any = any
+        .map(|any| any.iter().any(is_enabled))
+        .unwrap_or(true);
+    let has_all = all
+        .map(|all| all.iter().all(is_enabled))
+        .unwrap_or(true);
+    has_any && has_all
+}
+
+/// A version condition: either a range like `>=1.2, <1.3`, or a regex on the dotted version
+enum VersionPattern {
+    Range(crate::version::VersionReq),
+    Regex(libc_regex_sys::Regex),
+}
+
+/// Ranges start with a comparison operator, anything else is a regex
+fn maybe_version_pattern<S: AsRef<str>>(s: S) -> Option<VersionPattern> {
+    let s = s.as_ref();
+    if s.trim_start().starts_with(|c| "<>=~".contains(c)) {
+        s.parse()
+            .err_side_effect(|e| warn!("Invalid version range in config: {}", e))
+            .ok()
+            .map(VersionPattern::Range)
+    } else {
+        maybe_pattern(s).map(VersionPattern::Regex)
+    }
+}
+
+/// Returns `true` if every pattern of `all` matches one of `names`, and at least one of `any` does
+fn is_patterns_match(names: &[String], any: Option<&Vec<String>>, all: Option<&Vec<String>>) -> bool {
+    let is_present = |pattern: &String| {
+        maybe_pattern(pattern)
+            .map(|pattern| names.iter().any(|name| pattern.is_match(name)))
+            .unwrap_or(false)
+    };
+    let has_any = any
+        .map(|any| any.iter().any(is_present))
+        .unwrap_or(true);
+    let has_all = all
+        .map(|all| all.iter().all(is_present))
+        .unwrap_or(true);
+    has_any && has_all
+}
+
+/// Returns `true` if the user or group `id` is `expected_id`, and its name matches `name_pattern`
+fn is_id_match<F>(id: u32, lookup_name: F, name_pattern: Option<&String>, expected_id: Option<u32>) -> bool
+where
+    F: FnOnce(u32) -> Option<String>,
+{
+    if expected_id.map(|expected_id| expected_id != id).unwrap_or(false) {
+        return false;
+    }
+    match name_pattern {
+        Some(pattern) => maybe_pattern(pattern)
+            .and_then_zip(|| lookup_name(id))
+            .map(|(pattern, name)| pattern.is_match(&name))
+            .unwrap_or(false),
+        None => true,
+    }
+}
+
+fn version_match_excludes(pattern: &VersionPattern, version: Option<vk::VulkanSemanticVersion>) -> bool {
     version
-        .map(|v| format!("{}", v))
         .map(|real_version| {
-            if !pattern.is_match(&*real_version) {
-                true
-            } else {
-                false
+            match pattern {
+                VersionPattern::Range(range) => !range.matches(real_version),
+                VersionPattern::Regex(pattern) => !pattern.is_match(&*format!("{}", real_version)),
             }
         })
         .unwrap_or(true)
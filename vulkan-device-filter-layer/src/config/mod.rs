@@ -1,11 +1,17 @@
 use serde::{
+    de,
     Deserialize,
+    Deserializer,
     Serialize,
 };
 
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs,
     io,
+    num::NonZeroU32,
+    str,
     sync,
     path::{
         Path,
@@ -33,12 +39,542 @@ pub enum MatchRule {
     Or {
         rules: Vec<Box<MatchRule>>
     },
+    /// Matches on whether the system was on AC or battery power when the instance was created
+    Power {
+        source: crate::sysfs::PowerSource,
+    },
+    /// Matches on the extensions enabled in `VkInstanceCreateInfo`: all of `all`, and at least
+    /// one of `any`
+    InstanceExtensions {
+        #[serde(default)]
+        any: Option<Vec<String>>,
+        #[serde(default)]
+        all: Option<Vec<String>>,
+    },
+    /// Like `InstanceExtensions`, but for the layers enabled in `VkInstanceCreateInfo`
+    InstanceLayers {
+        #[serde(default)]
+        any: Option<Vec<String>>,
+        #[serde(default)]
+        all: Option<Vec<String>>,
+    },
+    /// Matches on the user running the application, by name (a regex) and/or uid
+    User {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        uid: Option<u32>,
+    },
+    /// Matches if one of the process's groups (primary or supplementary) has a matching name
+    /// and/or gid
+    Group {
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        gid: Option<u32>,
+    },
+    /// Matches on the kind of session the application runs in
+    Session {
+        #[serde(rename = "type")]
+        session_type: crate::process::SessionType,
+    },
+    /// Matches the Flatpak application id of a sandboxed application
+    Flatpak {
+        app_id: String,
+    },
+    /// Matches the name of the snap an application was installed from
+    Snap {
+        name: String,
+    },
+    /// Matches if any of the cgroups the process belongs to (such as the systemd unit or
+    /// container) matches `path`
+    Cgroup {
+        path: String,
+    },
+    /// Matches if the executable path or command name of an ancestor process matches `name`,
+    /// looking at most `max_depth` generations up (1 being the parent) from when the instance was
+    /// created
+    Ancestor {
+        name: String,
+        #[serde(default)]
+        max_depth: Option<usize>,
+    },
+    /// Matches the host name of the machine
+    Host {
+        name: String,
+    },
+    /// Matches on the names of all physical devices present in the machine, whether they're
+    /// filtered or not: every pattern in `all` must match one of them, and at least one pattern
+    /// in `any` must
+    PresentDevices {
+        #[serde(default)]
+        any: Option<Vec<String>>,
+        #[serde(default)]
+        all: Option<Vec<String>>,
+    },
+    /// Matches the working directory of the application when the instance was created
+    Cwd {
+        path: String,
+    },
+    /// Matches if `path` existed when the instance was created, relative to the working directory.
+    /// The file name may contain `*` and `?` wildcards.
+    FileExists {
+        path: String,
+    },
+    /// Matches if the instance was created on one of `days`, during one of the `hours` ranges
+    /// (like `09:00-17:30`), in local time
+    Schedule {
+        #[serde(default)]
+        days: Option<Vec<crate::schedule::Weekday>>,
+        #[serde(default)]
+        hours: Option<Vec<crate::schedule::TimeRange>>,
+    },
+}
+
+impl MatchRule {
+    /// Calls `f` on this rule and every rule nested in it
+    pub fn visit(&self, f: &mut dyn FnMut(&MatchRule)) {
+        f(self);
+        match self {
+            MatchRule::And { rules } | MatchRule::Or { rules } => {
+                rules.iter().for_each(|rule| rule.visit(f));
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Values reported in place of the real `VkPhysicalDeviceProperties` to matched applications.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PropertyOverrides {
+    pub device_name: Option<String>,
+    pub vendor_id: Option<u32>,
+    pub device_id: Option<u32>,
+    /// Raw driver version, in the vendor-specific encoding
+    pub driver_version: Option<u32>,
+    pub api_version: Option<String>,
+}
+
+/// Vulkan field name of a physical device feature, like `geometryShader`. Unknown names are
+/// rejected when the config is read.
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct FeatureName(String);
+
+impl<'de> Deserialize<'de> for FeatureName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use de::Error;
+        let name = String::deserialize(deserializer)?;
+        if !crate::features::is_known_feature(&name) {
+            return Err(D::Error::custom(format!("unknown physical device feature: {}", &name)));
+        }
+        Ok(FeatureName(name))
+    }
+}
+
+impl AsRef<str> for FeatureName {
+    #[inline(always)]
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+/// A size in bytes, written either as a plain number or with a binary unit suffix, like `8GiB`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct ByteSize(pub u64);
+
+impl str::FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (value, unit) = s.split_at(split);
+        let value: u64 = value.parse()
+            .map_err(|_| format!("invalid size: {:?}", s))?;
+        let shift = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 0,
+            "k" | "kib" => 10,
+            "m" | "mib" => 20,
+            "g" | "gib" => 30,
+            "t" | "tib" => 40,
+            _ => return Err(format!("invalid size unit: {:?}", unit)),
+        };
+        value.checked_mul(1 << shift)
+            .map(ByteSize)
+            .ok_or_else(|| format!("size too large: {:?}", s))
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ByteSizeVisitor;
+
+        impl<'de> de::Visitor<'de> for ByteSizeVisitor {
+            type Value = ByteSize;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a size in bytes, optionally with a unit like 'GiB'")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<ByteSize, E> {
+                Ok(ByteSize(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<ByteSize, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(ByteSizeVisitor)
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Scalar field name of `VkPhysicalDeviceLimits`, like `maxComputeSharedMemorySize`. Unknown names
+/// are rejected when the config is read.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct LimitName(String);
+
+impl<'de> Deserialize<'de> for LimitName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use de::Error;
+        let name = String::deserialize(deserializer)?;
+        if !crate::limits::is_known_limit(&name) {
+            return Err(D::Error::custom(format!("unknown physical device limit: {}", &name)));
+        }
+        Ok(LimitName(name))
+    }
+}
+
+impl AsRef<str> for LimitName {
+    #[inline(always)]
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    AtLeast,
+    AtMost,
+    Equal,
+}
+
+/// A numeric condition, written like `>= 8GiB`, `<= 4` or `== 1`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison<T> {
+    op: CompareOp,
+    value: T,
+}
+
+impl<T: PartialOrd> Comparison<T> {
+    pub fn is_match(&self, value: &T) -> bool {
+        match self.op {
+            CompareOp::AtLeast => value >= &self.value,
+            CompareOp::AtMost => value <= &self.value,
+            CompareOp::Equal => value == &self.value,
+        }
+    }
+}
+
+impl<T> str::FromStr for Comparison<T> where
+    T: str::FromStr,
+    T::Err: fmt::Display,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (op, value) = if s.starts_with(">=") {
+            (CompareOp::AtLeast, &s[2..])
+        } else if s.starts_with("<=") {
+            (CompareOp::AtMost, &s[2..])
+        } else if s.starts_with("==") {
+            (CompareOp::Equal, &s[2..])
+        } else {
+            return Err(format!("comparison must start with '>=', '<=' or '==': {:?}", s));
+        };
+        value.trim().parse()
+            .map(|value| Comparison {
+                op: op,
+                value: value,
+            })
+            .map_err(|e| format!("invalid comparison {:?}: {}", s, e))
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Comparison<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.op {
+            CompareOp::AtLeast => ">=",
+            CompareOp::AtMost => "<=",
+            CompareOp::Equal => "==",
+        };
+        write!(f, "{} {}", op, &self.value)
+    }
+}
+
+impl<T: fmt::Display> Serialize for Comparison<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Comparison<T> where
+    T: str::FromStr,
+    T::Err: fmt::Display,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use de::Error;
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// `VkDriverId` values, by the name of the driver (`VK_DRIVER_ID_MESA_RADV` is `mesa_radv`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum DriverId {
+    AmdProprietary = 1,
+    AmdOpenSource = 2,
+    MesaRadv = 3,
+    NvidiaProprietary = 4,
+    IntelProprietaryWindows = 5,
+    IntelOpenSourceMesa = 6,
+    ImaginationProprietary = 7,
+    QualcommProprietary = 8,
+    ArmProprietary = 9,
+    GoogleSwiftshader = 10,
+    GgpProprietary = 11,
+    BroadcomProprietary = 12,
+    MesaLlvmpipe = 13,
+    Moltenvk = 14,
+    CoreaviProprietary = 15,
+    JuiceProprietary = 16,
+    VerisiliconProprietary = 17,
+    MesaTurnip = 18,
+    MesaV3dv = 19,
+    MesaPanvk = 20,
+    SamsungProprietary = 21,
+    MesaVenus = 22,
+    MesaDozen = 23,
+    MesaNvk = 24,
+    ImaginationOpenSourceMesa = 25,
+    MesaAgxv = 26,
+}
+
+/// `VkPhysicalDeviceType` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum DeviceType {
+    Other = 0,
+    Integrated = 1,
+    Discrete = 2,
+    Virtual = 3,
+    Cpu = 4,
+}
+
+/// Key for ordering the devices reported to an application, best first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Device types in order of preference. Unlisted types go last.
+    Type(Vec<DeviceType>),
+    /// Most device-local memory first
+    Vram,
+    /// PCI vendor ids in order of preference. Unlisted vendors go last.
+    Vendor(Vec<u32>),
+    /// Devices driving a connected display first, then the boot VGA device. Needs
+    /// `VK_EXT_physical_device_drm` to tell which DRM card a device is.
+    Display,
+}
+
+/// Picks out some of the devices a filter exposes, for `prefer`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceSelector {
+    /// Devices connected externally, like Thunderbolt eGPUs
+    External,
+    Type(DeviceType),
+    /// Devices whose name matches a pattern
+    Name(String),
+    /// Devices with a PCI vendor id
+    Vendor(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceMode {
+    /// Take turns, using a counter shared between processes
+    RoundRobin,
+    /// The device with the lowest busy percentage, then the least VRAM in use
+    LeastLoaded,
+}
+
+/// Stable order of devices to pick from for `Selection::Hash`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashOrder {
+    /// By `deviceUUID`, which needs Vulkan 1.1
+    Uuid,
+    /// By PCI address, which needs `VK_EXT_pci_bus_info`
+    Pci,
+}
+
+impl Default for HashOrder {
+    fn default() -> Self {
+        HashOrder::Pci
+    }
+}
+
+/// Strategy for exposing a single device out of the devices a filter matches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selection {
+    /// Spread instances across the matching devices
+    Balance(BalanceMode),
+    /// Pick device number `hash(value of env) mod N`, out of the N matching devices
+    Hash {
+        env: String,
+        #[serde(default)]
+        order: HashOrder,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueFlag {
+    Graphics,
+    Compute,
+    Transfer,
+    SparseBinding,
+    Protected,
+    VideoDecode,
+    VideoEncode,
+}
+
+/// Rule applied to every queue family that has all of `with_flags`, and none of `without_flags`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueRule {
+    #[serde(default)]
+    with_flags: Vec<QueueFlag>,
+    #[serde(default)]
+    without_flags: Vec<QueueFlag>,
+    #[serde(default)]
+    hide: bool,
+    /// Must be at least 1, since a family can't be reported without any queues
+    max_count: Option<NonZeroU32>,
+}
+
+impl QueueRule {
+    #[inline(always)]
+    pub fn with_flags(&self) -> &[QueueFlag] {
+        self.with_flags.as_slice()
+    }
+
+    #[inline(always)]
+    pub fn without_flags(&self) -> &[QueueFlag] {
+        self.without_flags.as_slice()
+    }
+
+    #[inline(always)]
+    pub fn hide(&self) -> bool {
+        self.hide
+    }
+
+    #[inline(always)]
+    pub fn max_count(&self) -> Option<u32> {
+        self.max_count.map(NonZeroU32::get)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Filter {
     match_rule: MatchRule,
-    filter: String,
+    /// Only devices whose name matches this pattern are exposed
+    #[serde(default)]
+    filter: Option<String>,
+    #[serde(default)]
+    properties: Option<PropertyOverrides>,
+    /// Clamps the reported instance and physical device API versions
+    #[serde(default)]
+    max_api_version: Option<String>,
+    /// Device extensions matching any of these patterns are hidden
+    #[serde(default)]
+    hide_extensions: Option<Vec<String>>,
+    /// If present, only device extensions matching one of these patterns are exposed
+    #[serde(default)]
+    only_extensions: Option<Vec<String>>,
+    /// Physical device features reported as unsupported
+    #[serde(default)]
+    disable_features: Option<Vec<FeatureName>>,
+    /// Clamps the reported size of each device-local memory heap
+    #[serde(default)]
+    max_device_local_memory: Option<ByteSize>,
+    /// If set, device-local allocations past `max_device_local_memory` fail with
+    /// `VK_ERROR_OUT_OF_DEVICE_MEMORY`
+    #[serde(default)]
+    limit_allocations: Option<bool>,
+    /// Rules for hiding queue families, or capping their queue counts
+    #[serde(default)]
+    queues: Option<Vec<QueueRule>>,
+    /// Only devices supporting all of these extensions are exposed
+    #[serde(default)]
+    requires_extensions: Option<Vec<String>>,
+    /// Only devices supporting all of these features are exposed
+    #[serde(default)]
+    requires_features: Option<Vec<FeatureName>>,
+    /// Only devices whose `VkPhysicalDeviceLimits` satisfy all of these comparisons are exposed
+    #[serde(default)]
+    limits: Option<BTreeMap<LimitName, Comparison<f64>>>,
+    /// Only devices whose total device-local heap size satisfies this comparison are exposed
+    #[serde(default)]
+    device_local_memory: Option<Comparison<ByteSize>>,
+    /// Fallback chain of selectors. Only the devices matching the first selector that matches
+    /// anything are exposed.
+    #[serde(default)]
+    prefer: Option<Vec<DeviceSelector>>,
+    /// Order in which devices (and device groups) are reported, with ties broken by PCI address
+    #[serde(default)]
+    sort_by: Option<Vec<SortKey>>,
+    /// Exposes only one of the matching devices, picked once per instance
+    #[serde(default)]
+    select: Option<Selection>,
+    /// Overrides the global `hide_cpu_devices` setting
+    #[serde(default)]
+    hide_cpu_devices: Option<bool>,
+    /// Only devices using this driver are exposed
+    #[serde(default)]
+    driver_id: Option<DriverId>,
+    /// Only devices whose `driverName` matches this pattern are exposed
+    #[serde(default)]
+    driver_name: Option<String>,
+    /// Only devices whose `driverInfo` matches this pattern are exposed
+    #[serde(default)]
+    driver_info: Option<String>,
 }
 
 impl Filter {
@@ -48,14 +584,123 @@ impl Filter {
     }
 
     #[inline(always)]
-    pub fn filter(&self) -> &str {
-        self.filter.as_ref()
+    pub fn filter(&self) -> Option<&str> {
+        self.filter.as_ref().map(|s| s.as_str())
+    }
+
+    #[inline(always)]
+    pub fn properties(&self) -> Option<&PropertyOverrides> {
+        self.properties.as_ref()
+    }
+
+    #[inline(always)]
+    pub fn max_api_version(&self) -> Option<&str> {
+        self.max_api_version.as_ref().map(|s| s.as_ref())
+    }
+
+    #[inline(always)]
+    pub fn hide_extensions(&self) -> Option<&[String]> {
+        self.hide_extensions.as_ref().map(|v| v.as_slice())
+    }
+
+    #[inline(always)]
+    pub fn only_extensions(&self) -> Option<&[String]> {
+        self.only_extensions.as_ref().map(|v| v.as_slice())
+    }
+
+    #[inline(always)]
+    pub fn disable_features(&self) -> Option<&[FeatureName]> {
+        self.disable_features.as_ref().map(|v| v.as_slice())
+    }
+
+    #[inline(always)]
+    pub fn max_device_local_memory(&self) -> Option<u64> {
+        self.max_device_local_memory.map(|size| size.0)
+    }
+
+    #[inline(always)]
+    pub fn limit_allocations(&self) -> bool {
+        self.limit_allocations.unwrap_or(false)
+    }
+
+    #[inline(always)]
+    pub fn queues(&self) -> Option<&[QueueRule]> {
+        self.queues.as_ref().map(|v| v.as_slice())
+    }
+
+    #[inline(always)]
+    pub fn requires_extensions(&self) -> Option<&[String]> {
+        self.requires_extensions.as_ref().map(|v| v.as_slice())
+    }
+
+    #[inline(always)]
+    pub fn requires_features(&self) -> Option<&[FeatureName]> {
+        self.requires_features.as_ref().map(|v| v.as_slice())
+    }
+
+    #[inline(always)]
+    pub fn limits(&self) -> Option<&BTreeMap<LimitName, Comparison<f64>>> {
+        self.limits.as_ref()
+    }
+
+    #[inline(always)]
+    pub fn device_local_memory(&self) -> Option<&Comparison<ByteSize>> {
+        self.device_local_memory.as_ref()
+    }
+
+    #[inline(always)]
+    pub fn prefer(&self) -> Option<&[DeviceSelector]> {
+        self.prefer.as_ref().map(|v| v.as_slice())
+    }
+
+    #[inline(always)]
+    pub fn sort_by(&self) -> Option<&[SortKey]> {
+        self.sort_by.as_ref().map(|v| v.as_slice())
+    }
+
+    #[inline(always)]
+    pub fn select(&self) -> Option<&Selection> {
+        self.select.as_ref()
+    }
+
+    #[inline(always)]
+    pub fn hide_cpu_devices(&self) -> Option<bool> {
+        self.hide_cpu_devices
+    }
+
+    #[inline(always)]
+    pub fn driver_id(&self) -> Option<DriverId> {
+        self.driver_id
+    }
+
+    #[inline(always)]
+    pub fn driver_name(&self) -> Option<&str> {
+        self.driver_name.as_ref().map(|s| s.as_str())
+    }
+
+    #[inline(always)]
+    pub fn driver_info(&self) -> Option<&str> {
+        self.driver_info.as_ref().map(|s| s.as_str())
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     filters: Vec<Filter>,
+    /// If set, `vkCreateDevice` will refuse to create devices on physical devices that were
+    /// filtered out for the instance, instead of just hiding them from enumeration.
+    #[serde(default)]
+    enforce: Option<bool>,
+    /// Hide `VK_PHYSICAL_DEVICE_TYPE_CPU` devices whenever a hardware device is visible. Filters
+    /// may override this.
+    #[serde(default)]
+    hide_cpu_devices: Option<bool>,
+    /// Where sysfs is mounted, `/sys` by default
+    #[serde(default)]
+    sysfs_root: Option<PathBuf>,
+    /// Root of the filesystem holding `/.flatpak-info` and `/proc`, `/` by default
+    #[serde(default)]
+    fs_root: Option<PathBuf>,
 }
 
 #[inline]
@@ -131,6 +776,35 @@ impl Config {
         self.filters.iter()
     }
 
+    /// Calls `f` on every match rule of every filter, including nested ones
+    pub fn visit_rules(&self, f: &mut dyn FnMut(&MatchRule)) {
+        self.filters().for_each(|filter| filter.match_rule().visit(f));
+    }
+
+    #[inline]
+    pub fn enforce(&self) -> bool {
+        self.enforce.unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn hide_cpu_devices(&self) -> bool {
+        self.hide_cpu_devices.unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn sysfs_root(&self) -> &Path {
+        self.sysfs_root.as_ref()
+            .map(|p| p.as_path())
+            .unwrap_or(Path::new("/sys"))
+    }
+
+    #[inline]
+    pub fn fs_root(&self) -> &Path {
+        self.fs_root.as_ref()
+            .map(|p| p.as_path())
+            .unwrap_or(Path::new("/"))
+    }
+
     fn read() -> Result<Self, serde_yaml::Error> {
         use std::env;
         if let Some(config_file) = env::var("VK_DEVICE_FILTER_CONFIG").ok() {
@@ -166,10 +840,18 @@ impl Config {
     fn new() -> Self {
         Config {
             filters: Vec::new(),
+            enforce: None,
+            hide_cpu_devices: None,
+            sysfs_root: None,
+            fs_root: None,
         }
     }
 
     fn merge(&mut self, other: Config) {
         self.filters.extend(other.filters);
+        self.enforce = self.enforce.or(other.enforce);
+        self.hide_cpu_devices = self.hide_cpu_devices.or(other.hide_cpu_devices);
+        self.sysfs_root = self.sysfs_root.take().or(other.sysfs_root);
+        self.fs_root = self.fs_root.take().or(other.fs_root);
     }
 }
@@ -0,0 +1,614 @@
+use ::vulkan_sys as sys;
+use crate::{
+    balance,
+    config::{
+        self,
+        Config,
+    },
+    dispatches,
+    features,
+    layer,
+    limits,
+    memory,
+    sysfs::{
+        self,
+        DeviceNumber,
+        DrmCard,
+    },
+    vk::VulkanSemanticVersion,
+    DispatchTableExt,
+    PhysicalDeviceGroupPropertiesExt,
+    PhysicalDevicePropertiesExt,
+    VulkanHandle,
+};
+use libc_regex_sys::{
+    Regex,
+    RegcompFlags,
+    RegcompFlagsBuilder,
+};
+use log::warn;
+use std::{
+    cmp::Ordering,
+    env,
+    ffi,
+    fmt,
+    mem,
+};
+
+/// Location of a PCI device, ordered the same way as the kernel lists them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PciAddress {
+    pub domain: u32,
+    pub bus: u32,
+    pub device: u32,
+    pub function: u32,
+}
+
+impl fmt::Display for PciAddress {
+    /// Formats the address the way sysfs names PCI devices, like `0000:01:00.0`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04x}:{:02x}:{:02x}.{:x}", self.domain, self.bus, self.device, self.function)
+    }
+}
+
+/// What we know about a physical device, queried once per device so that every selector can look
+/// at it without going back down the chain.
+#[derive(Clone)]
+pub struct DeviceInfo {
+    handle: sys::VkPhysicalDevice,
+    properties: sys::VkPhysicalDeviceProperties,
+    memory_properties: sys::VkPhysicalDeviceMemoryProperties,
+    extensions: Vec<String>,
+    features: features::DeviceFeatures,
+    /// Only available with Vulkan 1.2 or `VK_KHR_driver_properties`
+    driver: Option<sys::VkPhysicalDeviceDriverProperties>,
+    /// Only available with `VK_EXT_pci_bus_info`
+    pci_address: Option<PciAddress>,
+    /// Only available with Vulkan 1.1
+    uuid: Option<[u8; 16]>,
+    /// Only available with `VK_EXT_physical_device_drm`
+    drm_nodes: Vec<DeviceNumber>,
+}
+
+impl DeviceInfo {
+    /// Like `query`, but only goes down the chain the first time a device is seen. Devices are
+    /// forgotten when their instance is destroyed.
+    pub unsafe fn cached(dispatch: &layer::DispatchTable, physical_device: sys::VkPhysicalDevice) -> Self {
+        let key = physical_device.vulkan_handle_key();
+        let cached = dispatches::device_infos().read().unwrap()
+            .get(&key)
+            .and_then(|infos| infos.get(&(physical_device as usize)))
+            .cloned();
+        cached.unwrap_or_else(|| {
+            let info = DeviceInfo::query(dispatch, physical_device);
+            dispatches::device_infos().write().unwrap()
+                .entry(key)
+                .or_default()
+                .insert(physical_device as usize, info.clone());
+            info
+        })
+    }
+
+    pub unsafe fn query(dispatch: &layer::DispatchTable, physical_device: sys::VkPhysicalDevice) -> Self {
+        let properties = dispatch.physical_device_properties(physical_device);
+        let memory_properties = dispatch.physical_device_memory_properties(physical_device);
+        let extensions = dispatch.device_extension_names(physical_device);
+        let instance_version = dispatches::application_infos().read().unwrap()
+            .get(&physical_device.vulkan_handle_key())
+            .map(|info| info.api_version)
+            .unwrap_or(VulkanSemanticVersion::new(1, 0, 0));
+        let device_version = VulkanSemanticVersion::from_raw(properties.apiVersion);
+        let features = features::DeviceFeatures::query(dispatch, physical_device, instance_version, device_version, &extensions);
+        let has_properties2 = instance_version >= VulkanSemanticVersion::new(1, 1, 0);
+        let has_driver_properties = has_properties2 && (device_version >= VulkanSemanticVersion::new(1, 2, 0)
+            || extensions.iter().any(|e| e == "VK_KHR_driver_properties"));
+        let has_pci_bus_info = has_properties2 && extensions.iter().any(|e| e == "VK_EXT_pci_bus_info");
+        let has_drm = has_properties2 && extensions.iter().any(|e| e == "VK_EXT_physical_device_drm");
+        let mut driver: sys::VkPhysicalDeviceDriverProperties = mem::zeroed();
+        driver.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_DRIVER_PROPERTIES;
+        let mut pci_bus_info: sys::VkPhysicalDevicePCIBusInfoPropertiesEXT = mem::zeroed();
+        pci_bus_info.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PCI_BUS_INFO_PROPERTIES_EXT;
+        let has_id = has_properties2 && device_version >= VulkanSemanticVersion::new(1, 1, 0);
+        let mut id: sys::VkPhysicalDeviceIDProperties = mem::zeroed();
+        id.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_ID_PROPERTIES;
+        let mut drm: sys::VkPhysicalDeviceDrmPropertiesEXT = mem::zeroed();
+        drm.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_DRM_PROPERTIES_EXT;
+        let mut properties2: sys::VkPhysicalDeviceProperties2 = mem::zeroed();
+        properties2.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PROPERTIES_2;
+        // Only chain the structs the device knows about
+        if has_driver_properties {
+            driver.pNext = properties2.pNext;
+            properties2.pNext = &mut driver as *mut _ as *mut _;
+        }
+        if has_pci_bus_info {
+            pci_bus_info.pNext = properties2.pNext;
+            properties2.pNext = &mut pci_bus_info as *mut _ as *mut _;
+        }
+        if has_id {
+            id.pNext = properties2.pNext;
+            properties2.pNext = &mut id as *mut _ as *mut _;
+        }
+        if has_drm {
+            drm.pNext = properties2.pNext;
+            properties2.pNext = &mut drm as *mut _ as *mut _;
+        }
+        if !properties2.pNext.is_null() {
+            dispatch.get_physical_device_properties2(physical_device, &mut properties2);
+        }
+        DeviceInfo {
+            handle: physical_device,
+            properties: properties,
+            memory_properties: memory_properties,
+            extensions: extensions,
+            features: features,
+            driver: Some(driver).filter(|_| has_driver_properties),
+            pci_address: Some(pci_bus_info).filter(|_| has_pci_bus_info).map(|info| PciAddress {
+                domain: info.pciDomain,
+                bus: info.pciBus,
+                device: info.pciDevice,
+                function: info.pciFunction,
+            }),
+            uuid: Some(id.deviceUUID).filter(|_| has_id),
+            drm_nodes: Some(drm).filter(|_| has_drm).map(|drm| {
+                let primary = Some(DeviceNumber { major: drm.primaryMajor, minor: drm.primaryMinor })
+                    .filter(|_| drm.hasPrimary != 0);
+                let render = Some(DeviceNumber { major: drm.renderMajor, minor: drm.renderMinor })
+                    .filter(|_| drm.hasRender != 0);
+                primary.into_iter().chain(render).collect()
+            }).unwrap_or_default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn handle(&self) -> sys::VkPhysicalDevice {
+        self.handle
+    }
+
+    #[inline(always)]
+    pub fn is_cpu(&self) -> bool {
+        self.properties.deviceType == sys::VkPhysicalDeviceType_VK_PHYSICAL_DEVICE_TYPE_CPU
+    }
+
+    #[inline(always)]
+    pub fn name(&self) -> &ffi::CStr {
+        self.properties.get_name()
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|e| e == name)
+    }
+
+    #[inline(always)]
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.is_supported(name)
+    }
+
+    #[inline(always)]
+    pub fn limit(&self, name: &str) -> Option<f64> {
+        limits::limit(&self.properties.limits, name)
+    }
+
+    #[inline(always)]
+    pub fn device_local_memory(&self) -> u64 {
+        memory::device_local_size(&self.memory_properties)
+    }
+
+    #[inline(always)]
+    pub fn driver_id(&self) -> Option<sys::VkDriverId> {
+        self.driver.as_ref().map(|driver| driver.driverID)
+    }
+
+    pub fn driver_name(&self) -> Option<&ffi::CStr> {
+        self.driver.as_ref().map(|driver| unsafe { ffi::CStr::from_ptr(driver.driverName.as_ptr()) })
+    }
+
+    pub fn driver_info(&self) -> Option<&ffi::CStr> {
+        self.driver.as_ref().map(|driver| unsafe { ffi::CStr::from_ptr(driver.driverInfo.as_ptr()) })
+    }
+
+    #[inline(always)]
+    pub fn pci_address(&self) -> Option<PciAddress> {
+        self.pci_address
+    }
+
+    /// Finds the DRM card this device is, out of `cards`
+    pub fn find_drm_card<'a>(&self, cards: &'a [DrmCard]) -> Option<&'a DrmCard> {
+        cards.iter()
+            .find(|card| self.drm_nodes.iter().any(|&node| card.has_node(node)))
+    }
+
+    #[inline(always)]
+    pub fn uuid(&self) -> Option<[u8; 16]> {
+        self.uuid
+    }
+
+    #[inline(always)]
+    pub fn device_type(&self) -> sys::VkPhysicalDeviceType {
+        self.properties.deviceType
+    }
+
+    #[inline(always)]
+    pub fn vendor_id(&self) -> u32 {
+        self.properties.vendorID
+    }
+}
+
+/// Decides which physical devices an instance gets to see
+pub struct DeviceFilter {
+    name: Option<Regex>,
+    driver_name: Option<Regex>,
+    driver_info: Option<Regex>,
+    filter: Option<&'static config::Filter>,
+}
+
+fn regex_flags() -> RegcompFlags {
+    RegcompFlagsBuilder::default()
+        .extended(true)
+        .into()
+}
+
+fn compile_pattern(pattern: &str) -> Option<Regex> {
+    let regex = Regex::new(pattern, regex_flags()).ok();
+    if regex.is_none() {
+        warn!("DeviceFilterLayer: invalid device filter regex: {:?}", pattern);
+    }
+    regex
+}
+
+fn is_c_str_match(regex: &Regex, s: Option<&ffi::CStr>) -> bool {
+    s.and_then(|s| s.to_str().ok())
+        .map(|s| regex.is_match(s))
+        .unwrap_or(false)
+}
+
+impl DeviceFilter {
+    /// Gets the device filter for an instance (or any handle sharing its dispatch key). The
+    /// `VK_DEVICE_FILTER` environment variable takes precedence over the config file.
+    pub fn for_instance<H: VulkanHandle + Copy>(instance: H) -> Option<Self> {
+        let env_filter = env::var("VK_DEVICE_FILTER")
+            .ok()
+            .and_then(|ref s| Regex::new(s, regex_flags()).ok());
+        if env_filter.is_some() {
+            return Some(DeviceFilter {
+                name: env_filter,
+                driver_name: None,
+                driver_info: None,
+                filter: None,
+            });
+        }
+        crate::get_config_filter(instance)
+            .map(|f| DeviceFilter {
+                name: f.filter().and_then(compile_pattern),
+                driver_name: f.driver_name().and_then(compile_pattern),
+                driver_info: f.driver_info().and_then(compile_pattern),
+                filter: Some(f),
+            })
+    }
+
+    pub fn is_match(&self, device: &DeviceInfo) -> bool {
+        let name_matches = self.name.as_ref()
+            .map(|name| is_c_str_match(name, Some(device.name())))
+            .unwrap_or(true);
+        let driver_name_matches = self.driver_name.as_ref()
+            .map(|driver_name| is_c_str_match(driver_name, device.driver_name()))
+            .unwrap_or(true);
+        let driver_info_matches = self.driver_info.as_ref()
+            .map(|driver_info| is_c_str_match(driver_info, device.driver_info()))
+            .unwrap_or(true);
+        name_matches && driver_name_matches && driver_info_matches && self.filter.map(|f| is_selector_match(f, device)).unwrap_or(true)
+    }
+}
+
+/// Checks the requirements of a config filter beyond the device name
+fn is_selector_match(filter: &config::Filter, device: &DeviceInfo) -> bool {
+    let has_extensions = filter.requires_extensions()
+        .map(|extensions| extensions.iter().all(|e| device.has_extension(e)))
+        .unwrap_or(true);
+    let has_features = filter.requires_features()
+        .map(|features| features.iter().all(|f| device.has_feature(f.as_ref())))
+        .unwrap_or(true);
+    let has_limits = filter.limits()
+        .map(|limits| limits.iter().all(|(name, comparison)| {
+            device.limit(name.as_ref())
+                .map(|value| comparison.is_match(&value))
+                .unwrap_or(false)
+        }))
+        .unwrap_or(true);
+    let has_memory = filter.device_local_memory()
+        .map(|comparison| comparison.is_match(&config::ByteSize(device.device_local_memory())))
+        .unwrap_or(true);
+    let has_driver = filter.driver_id()
+        .map(|id| device.driver_id() == Some(id as sys::VkDriverId))
+        .unwrap_or(true);
+    has_extensions && has_features && has_limits && has_memory && has_driver
+}
+
+fn rank<T: PartialEq>(preferences: &[T], value: &T) -> usize {
+    preferences.iter()
+        .position(|p| p == value)
+        .unwrap_or(preferences.len())
+}
+
+/// Orders devices by the `sort_by` keys of a filter
+struct DeviceOrder {
+    keys: &'static [config::SortKey],
+    /// Only read if one of the keys needs it
+    drm_cards: Vec<DrmCard>,
+}
+
+impl DeviceOrder {
+    fn for_instance<H: VulkanHandle + Copy>(instance: H) -> Option<Self> {
+        use config::SortKey;
+        let keys = crate::get_config_filter(instance)
+            .and_then(|f| f.sort_by())?;
+        let drm_cards = if keys.iter().any(|key| matches!(key, SortKey::Display)) {
+            sysfs::drm_cards(Config::global().sysfs_root())
+                .unwrap_or_else(|e| {
+                    warn!("DeviceFilterLayer: failed to read DRM cards from sysfs: {}", &e);
+                    Vec::new()
+                })
+        } else {
+            Vec::new()
+        };
+        Some(DeviceOrder {
+            keys: keys,
+            drm_cards: drm_cards,
+        })
+    }
+
+    /// Lower is better: devices driving a display come first, then the boot VGA device
+    fn display_rank(&self, device: &DeviceInfo) -> (bool, bool) {
+        device.find_drm_card(&self.drm_cards)
+            .map(|card| (!card.connected, !card.boot_vga))
+            .unwrap_or((true, true))
+    }
+
+    fn compare_by_key(&self, key: &config::SortKey, a: &DeviceInfo, b: &DeviceInfo) -> Ordering {
+        use config::SortKey;
+        match key {
+            SortKey::Type(types) => {
+                let types: Vec<sys::VkPhysicalDeviceType> = types.iter()
+                    .map(|&ty| ty as sys::VkPhysicalDeviceType)
+                    .collect();
+                rank(&types, &a.device_type()).cmp(&rank(&types, &b.device_type()))
+            },
+            SortKey::Vram => b.device_local_memory().cmp(&a.device_local_memory()),
+            SortKey::Vendor(vendors) => rank(vendors, &a.vendor_id()).cmp(&rank(vendors, &b.vendor_id())),
+            SortKey::Display => self.display_rank(a).cmp(&self.display_rank(b)),
+        }
+    }
+
+    /// Compares devices by each key in turn, then by PCI address. Devices without a PCI address
+    /// go after the ones with one.
+    fn compare(&self, a: &DeviceInfo, b: &DeviceInfo) -> Ordering {
+        self.keys.iter()
+            .map(|key| self.compare_by_key(key, a, b))
+            .find(|&ordering| ordering != Ordering::Equal)
+            .unwrap_or_else(|| cmp_present_first(a.pci_address(), b.pci_address()))
+    }
+}
+
+fn hides_cpu_devices<H: VulkanHandle + Copy>(instance: H) -> bool {
+    crate::get_config_filter(instance)
+        .and_then(|f| f.hide_cpu_devices())
+        .unwrap_or_else(|| Config::global().hide_cpu_devices())
+}
+
+fn is_preferred(selector: &config::DeviceSelector, device: &DeviceInfo) -> bool {
+    use config::DeviceSelector;
+    match selector {
+        DeviceSelector::External => device.pci_address()
+            .map(|address| sysfs::is_external_pci_device(Config::global().sysfs_root(), &address.to_string()))
+            .unwrap_or(false),
+        DeviceSelector::Type(ty) => device.device_type() == *ty as sys::VkPhysicalDeviceType,
+        DeviceSelector::Name(pattern) => compile_pattern(pattern)
+            .map(|name| is_c_str_match(&name, Some(device.name())))
+            .unwrap_or(false),
+        DeviceSelector::Vendor(vendor_id) => device.vendor_id() == *vendor_id,
+    }
+}
+
+fn preferences<H: VulkanHandle + Copy>(instance: H) -> Option<&'static [config::DeviceSelector]> {
+    crate::get_config_filter(instance)
+        .and_then(|f| f.prefer())
+}
+
+/// Narrows `items` down to the ones matching the first selector in `preferences` that matches
+/// any of them. `is_match` checks a single item against a selector.
+fn apply_preferences<T, F>(preferences: &[config::DeviceSelector], items: &mut Vec<T>, is_match: F) where
+    F: Fn(&config::DeviceSelector, &T) -> bool,
+{
+    let selector = preferences.iter()
+        .find(|&selector| items.iter().any(|item| is_match(selector, item)));
+    if let Some(selector) = selector {
+        items.retain(|item| is_match(selector, item));
+    }
+}
+
+fn selection<H: VulkanHandle + Copy>(instance: H) -> Option<&'static config::Selection> {
+    use config::Selection;
+    crate::get_config_filter(instance)
+        .and_then(|f| f.select())
+        .filter(|selection| match selection {
+            Selection::Hash { env: key, .. } if env::var_os(key).is_none() => {
+                warn!("DeviceFilterLayer: {} is not set, not selecting a device by hash", key);
+                false
+            },
+            _ => true,
+        })
+}
+
+/// Orders `None` after any value, instead of before like `Option`'s `Ord` does
+fn cmp_present_first<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Picks a device out of `candidates` (which are in the order they'd be reported in)
+fn choose_device<'a>(selection: &config::Selection, candidates: &'a [DeviceInfo]) -> Option<&'a DeviceInfo> {
+    use config::{
+        BalanceMode,
+        HashOrder,
+        Selection,
+    };
+    if candidates.is_empty() {
+        return None;
+    }
+    match selection {
+        Selection::Balance(BalanceMode::RoundRobin) => {
+            let ticket = balance::next_ticket(balance::runtime_dir())
+                .unwrap_or_else(|e| {
+                    warn!("DeviceFilterLayer: failed to update round-robin counter: {}", &e);
+                    0
+                });
+            candidates.get((ticket % candidates.len() as u64) as usize)
+        },
+        Selection::Balance(BalanceMode::LeastLoaded) => {
+            let root = Config::global().sysfs_root();
+            // Devices without load counters are only picked if no device has them
+            candidates.iter()
+                .min_by_key(|device| {
+                    let load = device.pci_address()
+                        .map(|address| sysfs::gpu_load(root, &address.to_string()))
+                        .unwrap_or_default();
+                    (load.busy_percent.unwrap_or(u64::MAX), load.vram_used.unwrap_or(u64::MAX))
+                })
+        },
+        Selection::Hash { env: key, order } => {
+            let value = env::var(key).unwrap_or_default();
+            let mut sorted: Vec<&DeviceInfo> = candidates.iter().collect();
+            match order {
+                HashOrder::Uuid => sorted.sort_by(|a, b| cmp_present_first(a.uuid(), b.uuid())),
+                HashOrder::Pci => sorted.sort_by(|a, b| cmp_present_first(a.pci_address(), b.pci_address())),
+            }
+            let index = balance::stable_hash(&value) % sorted.len() as u64;
+            Some(sorted[index as usize])
+        },
+    }
+}
+
+/// The device picked by the `select` strategy of an instance's filter. The choice is made once per
+/// instance, and kept for as long as the device is still a candidate.
+fn selected_device<H: VulkanHandle + Copy>(instance: H, selection: &config::Selection, candidates: &[DeviceInfo]) -> Option<sys::VkPhysicalDevice> {
+    let mut selected_devices = dispatches::selected_devices().write().unwrap();
+    let key = instance.vulkan_handle_key();
+    let previous = selected_devices.get(&key)
+        .map(|&device| device)
+        .filter(|&device| candidates.iter().any(|info| info.handle() == device));
+    if previous.is_some() {
+        return previous;
+    }
+    let device = choose_device(selection, candidates)
+        .map(DeviceInfo::handle)?;
+    selected_devices.insert(key, device);
+    Some(device)
+}
+
+/// Picks the physical devices visible to an instance (or any handle sharing its dispatch key) out
+/// of all of the devices reported down the chain, keeping their order.
+pub unsafe fn visible_devices<H: VulkanHandle + Copy>(
+    instance: H,
+    dispatch: &layer::DispatchTable,
+    physical_devices: &[sys::VkPhysicalDevice]
+) -> Vec<sys::VkPhysicalDevice> {
+    let filter = DeviceFilter::for_instance(instance);
+    let hide_cpu_devices = hides_cpu_devices(instance);
+    let preferences = preferences(instance);
+    let order = DeviceOrder::for_instance(instance);
+    let selection = selection(instance);
+    if filter.is_none() && !hide_cpu_devices && preferences.is_none() && order.is_none() && selection.is_none() {
+        return physical_devices.to_vec();
+    }
+    let mut devices: Vec<DeviceInfo> = physical_devices.iter()
+        .map(|&d| DeviceInfo::cached(dispatch, d))
+        .filter(|info| filter.as_ref().map(|f| f.is_match(info)).unwrap_or(true))
+        .collect();
+    if hide_cpu_devices && devices.iter().any(|info| !info.is_cpu()) {
+        devices.retain(|info| !info.is_cpu());
+    }
+    if let Some(preferences) = preferences {
+        apply_preferences(preferences, &mut devices, is_preferred);
+    }
+    if let Some(order) = order {
+        devices.sort_by(|a, b| order.compare(a, b));
+    }
+    if let Some(selection) = selection {
+        let selected = selected_device(instance, selection, &devices);
+        devices.retain(|info| Some(info.handle()) == selected);
+    }
+    devices.iter()
+        .map(DeviceInfo::handle)
+        .collect()
+}
+
+/// Like `visible_devices`, but for device groups. A group is only visible if all of its members
+/// are.
+pub unsafe fn visible_groups<H: VulkanHandle + Copy>(
+    instance: H,
+    dispatch: &layer::DispatchTable,
+    groups: &[sys::VkPhysicalDeviceGroupProperties]
+) -> Vec<sys::VkPhysicalDeviceGroupProperties> {
+    let filter = DeviceFilter::for_instance(instance);
+    let hide_cpu_devices = hides_cpu_devices(instance);
+    let preferences = preferences(instance);
+    let order = DeviceOrder::for_instance(instance);
+    let selection = selection(instance);
+    if filter.is_none() && !hide_cpu_devices && preferences.is_none() && order.is_none() && selection.is_none() {
+        return groups.to_vec();
+    }
+    let mut groups: Vec<(sys::VkPhysicalDeviceGroupProperties, Vec<DeviceInfo>)> = groups.iter()
+        .map(|group| {
+            let members = group.physical_devices().iter()
+                .map(|&d| DeviceInfo::cached(dispatch, d))
+                .collect();
+            (*group, members)
+        })
+        .filter(|(_, members): &(_, Vec<DeviceInfo>)| {
+            filter.as_ref()
+                .map(|f| members.iter().all(|info| f.is_match(info)))
+                .unwrap_or(true)
+        })
+        .collect();
+    let is_cpu_group = |members: &[DeviceInfo]| members.iter().all(DeviceInfo::is_cpu);
+    if hide_cpu_devices && groups.iter().any(|(_, members)| !is_cpu_group(members)) {
+        groups.retain(|(_, members)| !is_cpu_group(members));
+    }
+    if let Some(preferences) = preferences {
+        apply_preferences(preferences, &mut groups, |selector, (_, members)| {
+            members.iter().all(|info| is_preferred(selector, info))
+        });
+    }
+    // Groups are ranked by their first member
+    if let Some(order) = order {
+        groups.sort_by(|(_, a), (_, b)| match (a.first(), b.first()) {
+            (Some(a), Some(b)) => order.compare(a, b),
+            _ => b.len().cmp(&a.len()),
+        });
+    }
+    if selection.is_some() {
+        // Only the selected device is exposed, so it gets a group of its own
+        let instance_handle = dispatches::instance_handles().read().unwrap()
+            .get(&instance.vulkan_handle_key())
+            .map(|&handle| handle);
+        let selected = instance_handle
+            .and_then(|handle| visible_devices(instance, dispatch, &dispatch.physical_devices(handle).unwrap_or_default()).first().map(|&d| d));
+        return groups.into_iter()
+            .map(|(group, _)| group)
+            .find(|group| group.physical_devices().iter().any(|&d| Some(d) == selected))
+            .map(|mut group| {
+                group.physicalDevices[0] = selected.unwrap();
+                group.physicalDeviceCount = 1;
+                group.subsetAllocation = 0;
+                group
+            })
+            .into_iter()
+            .collect();
+    }
+    groups.into_iter()
+        .map(|(group, _)| group)
+        .collect()
+}
@@ -0,0 +1,304 @@
+use ::vulkan_sys as sys;
+use crate::{
+    layer,
+    vk,
+};
+
+use std::mem;
+
+pub use crate::config::FeatureName;
+
+const VK_FALSE: sys::VkBool32 = 0;
+
+/// A struct made up of `VkBool32` feature fields, which can be looked up by their Vulkan names
+trait FeatureStruct {
+    const FIELDS: &'static [&'static str];
+
+    fn field(&self, name: &str) -> Option<sys::VkBool32>;
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut sys::VkBool32>;
+
+    fn disable<S: AsRef<str>>(&mut self, names: &[S]) {
+        for name in names {
+            if let Some(field) = self.field_mut(name.as_ref()) {
+                *field = VK_FALSE;
+            }
+        }
+    }
+}
+
+macro_rules! feature_struct {
+    ($ty:path { $($field:ident),* $(,)? }) => {
+        impl FeatureStruct for $ty {
+            const FIELDS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+            fn field(&self, name: &str) -> Option<sys::VkBool32> {
+                match name {
+                    $(stringify!($field) => Some(self.$field),)*
+                    _ => None,
+                }
+            }
+
+            fn field_mut(&mut self, name: &str) -> Option<&mut sys::VkBool32> {
+                match name {
+                    $(stringify!($field) => Some(&mut self.$field),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+feature_struct!(sys::VkPhysicalDeviceFeatures {
+    robustBufferAccess,
+    fullDrawIndexUint32,
+    imageCubeArray,
+    independentBlend,
+    geometryShader,
+    tessellationShader,
+    sampleRateShading,
+    dualSrcBlend,
+    logicOp,
+    multiDrawIndirect,
+    drawIndirectFirstInstance,
+    depthClamp,
+    depthBiasClamp,
+    fillModeNonSolid,
+    depthBounds,
+    wideLines,
+    largePoints,
+    alphaToOne,
+    multiViewport,
+    samplerAnisotropy,
+    textureCompressionETC2,
+    textureCompressionASTC_LDR,
+    textureCompressionBC,
+    occlusionQueryPrecise,
+    pipelineStatisticsQuery,
+    vertexPipelineStoresAndAtomics,
+    fragmentStoresAndAtomics,
+    shaderTessellationAndGeometryPointSize,
+    shaderImageGatherExtended,
+    shaderStorageImageExtendedFormats,
+    shaderStorageImageMultisample,
+    shaderStorageImageReadWithoutFormat,
+    shaderStorageImageWriteWithoutFormat,
+    shaderUniformBufferArrayDynamicIndexing,
+    shaderSampledImageArrayDynamicIndexing,
+    shaderStorageBufferArrayDynamicIndexing,
+    shaderStorageImageArrayDynamicIndexing,
+    shaderClipDistance,
+    shaderCullDistance,
+    shaderFloat64,
+    shaderInt64,
+    shaderInt16,
+    shaderResourceResidency,
+    shaderResourceMinLod,
+    sparseBinding,
+    sparseResidencyBuffer,
+    sparseResidencyImage2D,
+    sparseResidencyImage3D,
+    sparseResidency2Samples,
+    sparseResidency4Samples,
+    sparseResidency8Samples,
+    sparseResidency16Samples,
+    sparseResidencyAliased,
+    variableMultisampleRate,
+    inheritedQueries,
+});
+
+feature_struct!(sys::VkPhysicalDeviceVulkan11Features {
+    storageBuffer16BitAccess,
+    uniformAndStorageBuffer16BitAccess,
+    storagePushConstant16,
+    storageInputOutput16,
+    multiview,
+    multiviewGeometryShader,
+    multiviewTessellationShader,
+    variablePointersStorageBuffer,
+    variablePointers,
+    protectedMemory,
+    samplerYcbcrConversion,
+    shaderDrawParameters,
+});
+
+feature_struct!(sys::VkPhysicalDeviceVulkan12Features {
+    samplerMirrorClampToEdge,
+    drawIndirectCount,
+    storageBuffer8BitAccess,
+    uniformAndStorageBuffer8BitAccess,
+    storagePushConstant8,
+    shaderBufferInt64Atomics,
+    shaderSharedInt64Atomics,
+    shaderFloat16,
+    shaderInt8,
+    descriptorIndexing,
+    shaderInputAttachmentArrayDynamicIndexing,
+    shaderUniformTexelBufferArrayDynamicIndexing,
+    shaderStorageTexelBufferArrayDynamicIndexing,
+    shaderUniformBufferArrayNonUniformIndexing,
+    shaderSampledImageArrayNonUniformIndexing,
+    shaderStorageBufferArrayNonUniformIndexing,
+    shaderStorageImageArrayNonUniformIndexing,
+    shaderInputAttachmentArrayNonUniformIndexing,
+    shaderUniformTexelBufferArrayNonUniformIndexing,
+    shaderStorageTexelBufferArrayNonUniformIndexing,
+    descriptorBindingUniformBufferUpdateAfterBind,
+    descriptorBindingSampledImageUpdateAfterBind,
+    descriptorBindingStorageImageUpdateAfterBind,
+    descriptorBindingStorageBufferUpdateAfterBind,
+    descriptorBindingUniformTexelBufferUpdateAfterBind,
+    descriptorBindingStorageTexelBufferUpdateAfterBind,
+    descriptorBindingUpdateUnusedWhilePending,
+    descriptorBindingPartiallyBound,
+    descriptorBindingVariableDescriptorCount,
+    runtimeDescriptorArray,
+    samplerFilterMinmax,
+    scalarBlockLayout,
+    imagelessFramebuffer,
+    uniformBufferStandardLayout,
+    shaderSubgroupExtendedTypes,
+    separateDepthStencilLayouts,
+    hostQueryReset,
+    timelineSemaphore,
+    bufferDeviceAddress,
+    bufferDeviceAddressCaptureReplay,
+    bufferDeviceAddressMultiDevice,
+    vulkanMemoryModel,
+    vulkanMemoryModelDeviceScope,
+    vulkanMemoryModelAvailabilityVisibilityChains,
+    shaderOutputViewportIndex,
+    shaderOutputLayer,
+    subgroupBroadcastDynamicId,
+});
+
+feature_struct!(sys::VkPhysicalDeviceDescriptorIndexingFeatures {
+    shaderInputAttachmentArrayDynamicIndexing,
+    shaderUniformTexelBufferArrayDynamicIndexing,
+    shaderStorageTexelBufferArrayDynamicIndexing,
+    shaderUniformBufferArrayNonUniformIndexing,
+    shaderSampledImageArrayNonUniformIndexing,
+    shaderStorageBufferArrayNonUniformIndexing,
+    shaderStorageImageArrayNonUniformIndexing,
+    shaderInputAttachmentArrayNonUniformIndexing,
+    shaderUniformTexelBufferArrayNonUniformIndexing,
+    shaderStorageTexelBufferArrayNonUniformIndexing,
+    descriptorBindingUniformBufferUpdateAfterBind,
+    descriptorBindingSampledImageUpdateAfterBind,
+    descriptorBindingStorageImageUpdateAfterBind,
+    descriptorBindingStorageBufferUpdateAfterBind,
+    descriptorBindingUniformTexelBufferUpdateAfterBind,
+    descriptorBindingStorageTexelBufferUpdateAfterBind,
+    descriptorBindingUpdateUnusedWhilePending,
+    descriptorBindingPartiallyBound,
+    descriptorBindingVariableDescriptorCount,
+    runtimeDescriptorArray,
+});
+
+/// Returns `true` if `name` is a field of one of the feature structs we know how to handle
+pub fn is_known_feature(name: &str) -> bool {
+    [
+        <sys::VkPhysicalDeviceFeatures as FeatureStruct>::FIELDS,
+        <sys::VkPhysicalDeviceVulkan11Features as FeatureStruct>::FIELDS,
+        <sys::VkPhysicalDeviceVulkan12Features as FeatureStruct>::FIELDS,
+        <sys::VkPhysicalDeviceDescriptorIndexingFeatures as FeatureStruct>::FIELDS,
+    ].iter().any(|fields| fields.contains(&name))
+}
+
+pub fn disable_features<S: AsRef<str>>(features: &mut sys::VkPhysicalDeviceFeatures, names: &[S]) {
+    features.disable(names);
+}
+
+/// Disables the named features in `features`, and in any known feature structs in its `pNext`
+/// chain. The same name is disabled everywhere it appears, since some features (like the
+/// descriptor indexing ones) are reported in more than one struct.
+pub unsafe fn disable_features2<S: AsRef<str>>(features: &mut sys::VkPhysicalDeviceFeatures2, names: &[S]) {
+    features.features.disable(names);
+    let head: &mut vk::VkStructHead = mem::transmute(features);
+    for s in head.iter_mut() {
+        match s.ty() {
+            sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_1_FEATURES => {
+                let s: &mut sys::VkPhysicalDeviceVulkan11Features = mem::transmute(s);
+                s.disable(names);
+            },
+            sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_2_FEATURES => {
+                let s: &mut sys::VkPhysicalDeviceVulkan12Features = mem::transmute(s);
+                s.disable(names);
+            },
+            sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_DESCRIPTOR_INDEXING_FEATURES => {
+                let s: &mut sys::VkPhysicalDeviceDescriptorIndexingFeatures = mem::transmute(s);
+                s.disable(names);
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Features supported by a physical device, for matching devices against
+#[derive(Clone)]
+pub struct DeviceFeatures {
+    core: sys::VkPhysicalDeviceFeatures,
+    vulkan11: Option<sys::VkPhysicalDeviceVulkan11Features>,
+    vulkan12: Option<sys::VkPhysicalDeviceVulkan12Features>,
+    descriptor_indexing: Option<sys::VkPhysicalDeviceDescriptorIndexingFeatures>,
+}
+
+impl DeviceFeatures {
+    /// Queries the features of `physical_device`. The chained feature structs are only queried if
+    /// both the instance and device versions (or device extensions) allow it, and are otherwise
+    /// treated as unsupported.
+    pub unsafe fn query(
+        dispatch: &layer::DispatchTable,
+        physical_device: sys::VkPhysicalDevice,
+        instance_version: vk::VulkanSemanticVersion,
+        device_version: vk::VulkanSemanticVersion,
+        extensions: &[String]
+    ) -> Self {
+        let mut core: sys::VkPhysicalDeviceFeatures = mem::zeroed();
+        dispatch.get_physical_device_features(physical_device, &mut core);
+        let v1_1 = vk::VulkanSemanticVersion::new(1, 1, 0);
+        let v1_2 = vk::VulkanSemanticVersion::new(1, 2, 0);
+        if instance_version < v1_1 || device_version < v1_1 {
+            return DeviceFeatures {
+                core: core,
+                vulkan11: None,
+                vulkan12: None,
+                descriptor_indexing: None,
+            };
+        }
+        let mut vulkan11: sys::VkPhysicalDeviceVulkan11Features = mem::zeroed();
+        vulkan11.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_1_FEATURES;
+        let mut vulkan12: sys::VkPhysicalDeviceVulkan12Features = mem::zeroed();
+        vulkan12.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_2_FEATURES;
+        let mut descriptor_indexing: sys::VkPhysicalDeviceDescriptorIndexingFeatures = mem::zeroed();
+        descriptor_indexing.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_DESCRIPTOR_INDEXING_FEATURES;
+        let mut features2: sys::VkPhysicalDeviceFeatures2 = mem::zeroed();
+        features2.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_FEATURES_2;
+        let is_vulkan12 = device_version >= v1_2;
+        let has_descriptor_indexing = extensions.iter().any(|e| e == "VK_EXT_descriptor_indexing");
+        if is_vulkan12 {
+            vulkan12.pNext = &mut vulkan11 as *mut _ as *mut _;
+            features2.pNext = &mut vulkan12 as *mut _ as *mut _;
+        } else if has_descriptor_indexing {
+            features2.pNext = &mut descriptor_indexing as *mut _ as *mut _;
+        }
+        dispatch.get_physical_device_features2(physical_device, &mut features2);
+        DeviceFeatures {
+            core: core,
+            vulkan11: Some(vulkan11).filter(|_| is_vulkan12),
+            vulkan12: Some(vulkan12).filter(|_| is_vulkan12),
+            descriptor_indexing: Some(descriptor_indexing).filter(|_| !is_vulkan12 && has_descriptor_indexing),
+        }
+    }
+
+    /// Returns `true` if any of the queried feature structs reports `name` as supported
+    pub fn is_supported(&self, name: &str) -> bool {
+        let fields = [
+            self.core.field(name),
+            self.vulkan11.as_ref().and_then(|s| s.field(name)),
+            self.vulkan12.as_ref().and_then(|s| s.field(name)),
+            self.descriptor_indexing.as_ref().and_then(|s| s.field(name)),
+        ];
+        fields.iter().any(|&field| field.map(|v| v != VK_FALSE).unwrap_or(false))
+    }
+}
@@ -10,6 +10,13 @@ pub struct DispatchTable {
     pfn_enumerate_device_extension_properties: sys::PFN_vkEnumerateDeviceExtensionProperties,
     pfn_enumerate_physical_devices: sys::PFN_vkEnumeratePhysicalDevices,
     pfn_get_physical_device_properties: sys::PFN_vkGetPhysicalDeviceProperties,
+    pfn_get_physical_device_properties2: sys::PFN_vkGetPhysicalDeviceProperties2,
+    pfn_get_physical_device_features: sys::PFN_vkGetPhysicalDeviceFeatures,
+    pfn_get_physical_device_features2: sys::PFN_vkGetPhysicalDeviceFeatures2,
+    pfn_get_physical_device_memory_properties: sys::PFN_vkGetPhysicalDeviceMemoryProperties,
+    pfn_get_physical_device_memory_properties2: sys::PFN_vkGetPhysicalDeviceMemoryProperties2,
+    pfn_get_physical_device_queue_family_properties: sys::PFN_vkGetPhysicalDeviceQueueFamilyProperties,
+    pfn_get_physical_device_queue_family_properties2: sys::PFN_vkGetPhysicalDeviceQueueFamilyProperties2,
     pfn_enumerate_physical_device_groups: sys::PFN_vkEnumeratePhysicalDeviceGroups,
 }
 
@@ -17,6 +24,8 @@ pub struct DispatchTable {
 pub struct DeviceDispatchTable {
     pfn_get_device_proc_addr: sys::PFN_vkGetDeviceProcAddr,
     pfn_destroy_device: sys::PFN_vkDestroyDevice,
+    pfn_allocate_memory: sys::PFN_vkAllocateMemory,
+    pfn_free_memory: sys::PFN_vkFreeMemory,
 }
 
 mod names {
@@ -25,6 +34,22 @@ mod names {
     pub const ENUMERATE_DEVICE_EXTENSION_PROPERTIES: &'static [u8] = b"vkEnumerateDeviceExtensionProperties\0";
     pub const ENUMERATE_PHYSICAL_DEVICES: &'static [u8] = b"vkEnumeratePhysicalDevices\0";
     pub const GET_PHYSICAL_DEVICE_PROPERTIES: &'static [u8] = b"vkGetPhysicalDeviceProperties\0";
+    pub const GET_PHYSICAL_DEVICE_PROPERTIES_2: &'static [u8] = b"vkGetPhysicalDeviceProperties2\0";
+    pub const GET_PHYSICAL_DEVICE_PROPERTIES_2_KHR: &'static [u8] = b"vkGetPhysicalDeviceProperties2KHR\0";
+    pub const GET_PHYSICAL_DEVICE_FEATURES: &'static [u8] = b"vkGetPhysicalDeviceFeatures\0";
+    pub const GET_PHYSICAL_DEVICE_FEATURES_2: &'static [u8] = b"vkGetPhysicalDeviceFeatures2\0";
+    pub const GET_PHYSICAL_DEVICE_FEATURES_2_KHR: &'static [u8] = b"vkGetPhysicalDeviceFeatures2KHR\0";
+    pub const GET_PHYSICAL_DEVICE_MEMORY_PROPERTIES: &'static [u8] = b"vkGetPhysicalDeviceMemoryProperties\0";
+    pub const GET_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2: &'static [u8] = b"vkGetPhysicalDeviceMemoryProperties2\0";
+    pub const GET_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2_KHR: &'static [u8] = b"vkGetPhysicalDeviceMemoryProperties2KHR\0";
+    pub const GET_PHYSICAL_DEVICE_QUEUE_FAMILY_PROPERTIES: &'static [u8] = b"vkGetPhysicalDeviceQueueFamilyProperties\0";
+    pub const GET_PHYSICAL_DEVICE_QUEUE_FAMILY_PROPERTIES_2: &'static [u8] = b"vkGetPhysicalDeviceQueueFamilyProperties2\0";
+    pub const GET_PHYSICAL_DEVICE_QUEUE_FAMILY_PROPERTIES_2_KHR: &'static [u8] = b"vkGetPhysicalDeviceQueueFamilyProperties2KHR\0";
+
+    // Device
+    pub const DESTROY_DEVICE: &'static [u8] = b"vkDestroyDevice\0";
+    pub const ALLOCATE_MEMORY: &'static [u8] = b"vkAllocateMemory\0";
+    pub const FREE_MEMORY: &'static [u8] = b"vkFreeMemory\0";
 }
 
 impl DispatchTable {
@@ -38,6 +63,13 @@ impl DispatchTable {
             pfn_enumerate_device_extension_properties: mem::transmute(load(names::ENUMERATE_DEVICE_EXTENSION_PROPERTIES)),
             pfn_enumerate_physical_devices: mem::transmute(load(names::ENUMERATE_PHYSICAL_DEVICES)),
             pfn_get_physical_device_properties: mem::transmute(load(names::GET_PHYSICAL_DEVICE_PROPERTIES)),
+            pfn_get_physical_device_properties2: mem::transmute(load(names::GET_PHYSICAL_DEVICE_PROPERTIES_2).or_else(|| load(names::GET_PHYSICAL_DEVICE_PROPERTIES_2_KHR))),
+            pfn_get_physical_device_features: mem::transmute(load(names::GET_PHYSICAL_DEVICE_FEATURES)),
+            pfn_get_physical_device_features2: mem::transmute(load(names::GET_PHYSICAL_DEVICE_FEATURES_2).or_else(|| load(names::GET_PHYSICAL_DEVICE_FEATURES_2_KHR))),
+            pfn_get_physical_device_memory_properties: mem::transmute(load(names::GET_PHYSICAL_DEVICE_MEMORY_PROPERTIES)),
+            pfn_get_physical_device_memory_properties2: mem::transmute(load(names::GET_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2).or_else(|| load(names::GET_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2_KHR))),
+            pfn_get_physical_device_queue_family_properties: mem::transmute(load(names::GET_PHYSICAL_DEVICE_QUEUE_FAMILY_PROPERTIES)),
+            pfn_get_physical_device_queue_family_properties2: mem::transmute(load(names::GET_PHYSICAL_DEVICE_QUEUE_FAMILY_PROPERTIES_2).or_else(|| load(names::GET_PHYSICAL_DEVICE_QUEUE_FAMILY_PROPERTIES_2_KHR))),
             pfn_enumerate_physical_device_groups: mem::transmute(load(b"vkEnumeratePhysicalDeviceGroups\0")),
         }
     }
@@ -50,6 +82,64 @@ impl DispatchTable {
         self.pfn_get_physical_device_properties.unwrap()(physical_device, properties);
     }
 
+    pub unsafe fn get_physical_device_properties2(
+        &self,
+        physical_device: vulkan_sys::VkPhysicalDevice,
+        properties: *mut vulkan_sys::VkPhysicalDeviceProperties2
+    ) {
+        self.pfn_get_physical_device_properties2.unwrap()(physical_device, properties);
+    }
+
+    pub unsafe fn get_physical_device_features(
+        &self,
+        physical_device: vulkan_sys::VkPhysicalDevice,
+        features: *mut vulkan_sys::VkPhysicalDeviceFeatures
+    ) {
+        self.pfn_get_physical_device_features.unwrap()(physical_device, features);
+    }
+
+    pub unsafe fn get_physical_device_features2(
+        &self,
+        physical_device: vulkan_sys::VkPhysicalDevice,
+        features: *mut vulkan_sys::VkPhysicalDeviceFeatures2
+    ) {
+        self.pfn_get_physical_device_features2.unwrap()(physical_device, features);
+    }
+
+    pub unsafe fn get_physical_device_memory_properties(
+        &self,
+        physical_device: vulkan_sys::VkPhysicalDevice,
+        memory_properties: *mut vulkan_sys::VkPhysicalDeviceMemoryProperties
+    ) {
+        self.pfn_get_physical_device_memory_properties.unwrap()(physical_device, memory_properties);
+    }
+
+    pub unsafe fn get_physical_device_memory_properties2(
+        &self,
+        physical_device: vulkan_sys::VkPhysicalDevice,
+        memory_properties: *mut vulkan_sys::VkPhysicalDeviceMemoryProperties2
+    ) {
+        self.pfn_get_physical_device_memory_properties2.unwrap()(physical_device, memory_properties);
+    }
+
+    pub unsafe fn get_physical_device_queue_family_properties(
+        &self,
+        physical_device: vulkan_sys::VkPhysicalDevice,
+        queue_family_property_count: *mut u32,
+        queue_family_properties: *mut vulkan_sys::VkQueueFamilyProperties
+    ) {
+        self.pfn_get_physical_device_queue_family_properties.unwrap()(physical_device, queue_family_property_count, queue_family_properties);
+    }
+
+    pub unsafe fn get_physical_device_queue_family_properties2(
+        &self,
+        physical_device: vulkan_sys::VkPhysicalDevice,
+        queue_family_property_count: *mut u32,
+        queue_family_properties: *mut vulkan_sys::VkQueueFamilyProperties2
+    ) {
+        self.pfn_get_physical_device_queue_family_properties2.unwrap()(physical_device, queue_family_property_count, queue_family_properties);
+    }
+
     pub unsafe fn enumerate_physical_device_groups(
         &self,
         instance: vulkan_sys::VkInstance,
@@ -95,7 +185,9 @@ impl DeviceDispatchTable {
         let mut load = move |name: &[u8]| load_fn(ffi::CStr::from_bytes_with_nul_unchecked(name));
         DeviceDispatchTable {
             pfn_get_device_proc_addr: pfn_get_device_proc_addr,
-            pfn_destroy_device: mem::transmute(load(b"vkDestroyDevice")),
+            pfn_destroy_device: mem::transmute(load(names::DESTROY_DEVICE)),
+            pfn_allocate_memory: mem::transmute(load(names::ALLOCATE_MEMORY)),
+            pfn_free_memory: mem::transmute(load(names::FREE_MEMORY)),
         }
     }
 
@@ -109,4 +201,18 @@ impl DeviceDispatchTable {
             .unwrap_or(ptr::null());
         self.pfn_destroy_device.unwrap()(device, allocation_callbacks);
     }
+
+    pub unsafe fn allocate_memory(
+        &self,
+        device: sys::VkDevice,
+        allocate_info: *const sys::VkMemoryAllocateInfo,
+        allocation_callbacks: *const sys::VkAllocationCallbacks,
+        memory: *mut sys::VkDeviceMemory
+    ) -> sys::VkResult {
+        self.pfn_allocate_memory.unwrap()(device, allocate_info, allocation_callbacks, memory)
+    }
+
+    pub unsafe fn free_memory(&self, device: sys::VkDevice, memory: sys::VkDeviceMemory, allocation_callbacks: *const sys::VkAllocationCallbacks) {
+        self.pfn_free_memory.unwrap()(device, memory, allocation_callbacks);
+    }
 }
@@ -9,11 +9,21 @@ extern crate log4rs;
 
 pub mod vk;
 pub mod version;
+pub mod balance;
+pub mod sysfs;
+pub mod process;
+pub mod schedule;
+pub mod memory;
+pub mod features;
+pub mod queues;
 mod layer;
 mod config;
+mod devices;
+mod limits;
 
 use config::Config;
 
+use log::warn;
 use std::{
     env,
     ffi,
@@ -25,21 +35,62 @@ use std::{
 
 pub(crate) mod dispatches {
     use std::collections::BTreeMap;
+    use std::path::PathBuf;
     use std::sync::{self, RwLock};
     use super::{
+        devices,
         layer,
+        memory,
+        process,
+        schedule,
+        sysfs,
         vk,
     };
 
     pub type ApplicationInfo = vk::ApplicationInfo<String, String>;
 
+    /// Extensions and layers an instance was created with
+    #[derive(Debug, Clone, Default)]
+    pub struct EnabledNames {
+        pub extensions: Vec<String>,
+        pub layers: Vec<String>,
+    }
+
+    /// State of the system when an instance was created, for the match rules that look at it.
+    /// Only what the configured rules use is recorded.
+    #[derive(Debug, Clone, Default)]
+    pub struct Snapshot {
+        pub power_source: Option<sysfs::PowerSource>,
+        /// Names of all the physical devices present, before filtering
+        pub present_devices: Option<Vec<String>>,
+        pub time: Option<schedule::LocalTime>,
+        /// Working directory of the application
+        pub cwd: Option<PathBuf>,
+        /// Whether each `FileExists` path existed, by path
+        pub existing_files: BTreeMap<String, bool>,
+        /// Every ancestor of the application, starting with its parent
+        pub ancestors: Option<Vec<process::ProcessInfo>>,
+    }
+
     static mut INSTANCE_DISPATCHES: Option<RwLock<BTreeMap<usize, layer::DispatchTable>>> = None;
     static mut DEVICE_DISPATCHES: Option<RwLock<BTreeMap<usize, layer::DeviceDispatchTable>>> = None;
     static mut APPLICATION_INFOS: Option<RwLock<BTreeMap<usize, ApplicationInfo>>> = None;
+    static mut ENABLED_NAMES: Option<RwLock<BTreeMap<usize, EnabledNames>>> = None;
+    static mut MEMORY_BUDGETS: Option<RwLock<BTreeMap<usize, memory::MemoryBudget>>> = None;
+    static mut INSTANCE_HANDLES: Option<RwLock<BTreeMap<usize, vk::Instance>>> = None;
+    static mut SNAPSHOTS: Option<RwLock<BTreeMap<usize, Snapshot>>> = None;
+    static mut DEVICE_INFOS: Option<RwLock<BTreeMap<usize, BTreeMap<usize, devices::DeviceInfo>>>> = None;
+    static mut SELECTED_DEVICES: Option<RwLock<BTreeMap<usize, vulkan_sys::VkPhysicalDevice>>> = None;
 
     static INIT_I_DISPATCHES: sync::Once = sync::Once::new();
     static INIT_D_DISPATCHES: sync::Once = sync::Once::new();
     static INIT_APPLICATION_INFOS: sync::Once = sync::Once::new();
+    static INIT_ENABLED_NAMES: sync::Once = sync::Once::new();
+    static INIT_MEMORY_BUDGETS: sync::Once = sync::Once::new();
+    static INIT_INSTANCE_HANDLES: sync::Once = sync::Once::new();
+    static INIT_SNAPSHOTS: sync::Once = sync::Once::new();
+    static INIT_DEVICE_INFOS: sync::Once = sync::Once::new();
+    static INIT_SELECTED_DEVICES: sync::Once = sync::Once::new();
 
     pub fn application_infos() -> &'static RwLock<BTreeMap<usize, ApplicationInfo>> {
         unsafe {
@@ -50,6 +101,15 @@ pub(crate) mod dispatches {
         }
     }
 
+    pub fn enabled_names() -> &'static RwLock<BTreeMap<usize, EnabledNames>> {
+        unsafe {
+            INIT_ENABLED_NAMES.call_once(|| {
+                ENABLED_NAMES = Some(RwLock::new(BTreeMap::new()));
+            });
+            ENABLED_NAMES.as_ref().unwrap()
+        }
+    }
+
     pub fn instances() -> &'static RwLock<BTreeMap<usize, layer::DispatchTable>> {
         unsafe {
             INIT_I_DISPATCHES.call_once(|| {
@@ -67,10 +127,66 @@ pub(crate) mod dispatches {
             DEVICE_DISPATCHES.as_ref().unwrap()
         }
     }
+
+    pub fn memory_budgets() -> &'static RwLock<BTreeMap<usize, memory::MemoryBudget>> {
+        unsafe {
+            INIT_MEMORY_BUDGETS.call_once(|| {
+                MEMORY_BUDGETS = Some(RwLock::new(BTreeMap::new()));
+            });
+            MEMORY_BUDGETS.as_ref().unwrap()
+        }
+    }
+
+    /// The instance that owns each dispatch key, so that physical device calls can look at the
+    /// other devices of their instance
+    pub fn instance_handles() -> &'static RwLock<BTreeMap<usize, vk::Instance>> {
+        unsafe {
+            INIT_INSTANCE_HANDLES.call_once(|| {
+                INSTANCE_HANDLES = Some(RwLock::new(BTreeMap::new()));
+            });
+            INSTANCE_HANDLES.as_ref().unwrap()
+        }
+    }
+
+    /// The state of the system each instance was created in
+    pub fn snapshots() -> &'static RwLock<BTreeMap<usize, Snapshot>> {
+        unsafe {
+            INIT_SNAPSHOTS.call_once(|| {
+                SNAPSHOTS = Some(RwLock::new(BTreeMap::new()));
+            });
+            SNAPSHOTS.as_ref().unwrap()
+        }
+    }
+
+    /// What is known about each physical device, by the dispatch key of its instance and then by
+    /// device handle
+    pub fn device_infos() -> &'static RwLock<BTreeMap<usize, BTreeMap<usize, devices::DeviceInfo>>> {
+        unsafe {
+            INIT_DEVICE_INFOS.call_once(|| {
+                DEVICE_INFOS = Some(RwLock::new(BTreeMap::new()));
+            });
+            DEVICE_INFOS.as_ref().unwrap()
+        }
+    }
+
+    /// The device picked for each instance by the `select` strategy of its filter
+    pub fn selected_devices() -> &'static RwLock<BTreeMap<usize, vulkan_sys::VkPhysicalDevice>> {
+        unsafe {
+            INIT_SELECTED_DEVICES.call_once(|| {
+                SELECTED_DEVICES = Some(RwLock::new(BTreeMap::new()));
+            });
+            SELECTED_DEVICES.as_ref().unwrap()
+        }
+    }
 }
 
 trait DispatchTableExt {
     fn physical_device_properties(&self, physical_device: vulkan_sys::VkPhysicalDevice) -> vulkan_sys::VkPhysicalDeviceProperties;
+    fn physical_device_memory_properties(&self, physical_device: vulkan_sys::VkPhysicalDevice) -> vulkan_sys::VkPhysicalDeviceMemoryProperties;
+    fn queue_family_properties(&self, physical_device: vulkan_sys::VkPhysicalDevice) -> Vec<vulkan_sys::VkQueueFamilyProperties>;
+    fn device_extension_names(&self, physical_device: vulkan_sys::VkPhysicalDevice) -> Vec<String>;
+    fn physical_devices(&self, instance: vk::Instance) -> Result<Vec<vulkan_sys::VkPhysicalDevice>, vk::Result>;
+    fn physical_device_groups(&self, instance: vk::Instance) -> Result<Vec<vulkan_sys::VkPhysicalDeviceGroupProperties>, vk::Result>;
 }
 
 impl DispatchTableExt for layer::DispatchTable {
@@ -81,10 +197,67 @@ impl DispatchTableExt for layer::DispatchTable {
             properties
         }
     }
+
+    fn physical_device_memory_properties(&self, physical_device: vulkan_sys::VkPhysicalDevice) -> vulkan_sys::VkPhysicalDeviceMemoryProperties {
+        unsafe {
+            let mut properties: vulkan_sys::VkPhysicalDeviceMemoryProperties = mem::zeroed();
+            self.get_physical_device_memory_properties(physical_device, &mut properties as *mut _);
+            properties
+        }
+    }
+
+    fn queue_family_properties(&self, physical_device: vulkan_sys::VkPhysicalDevice) -> Vec<vulkan_sys::VkQueueFamilyProperties> {
+        unsafe {
+            let mut count = 0;
+            self.get_physical_device_queue_family_properties(physical_device, &mut count, ptr::null_mut());
+            let mut properties: Vec<vulkan_sys::VkQueueFamilyProperties> = iter::repeat(mem::zeroed())
+                .take(count as usize)
+                .collect();
+            self.get_physical_device_queue_family_properties(physical_device, &mut count, properties.as_mut_ptr());
+            properties.truncate(count as usize);
+            properties
+        }
+    }
+
+    fn device_extension_names(&self, physical_device: vulkan_sys::VkPhysicalDevice) -> Vec<String> {
+        unsafe {
+            let mut count = 0;
+            if self.enumerate_device_extension_properties(physical_device, ptr::null(), &mut count, ptr::null_mut()) != vulkan_sys::VkResult_VK_SUCCESS {
+                return Vec::new();
+            }
+            let mut properties: Vec<vulkan_sys::VkExtensionProperties> = iter::repeat(mem::zeroed())
+                .take(count as usize)
+                .collect();
+            if !self.enumerate_device_extension_properties(physical_device, ptr::null(), &mut count, properties.as_mut_ptr()).is_success_or_incomplete() {
+                return Vec::new();
+            }
+            properties.truncate(count as usize);
+            properties.iter()
+                .map(|p| p.get_name().to_string_lossy().into_owned())
+                .collect()
+        }
+    }
+
+    fn physical_devices(&self, instance: vk::Instance) -> Result<Vec<vulkan_sys::VkPhysicalDevice>, vk::Result> {
+        unsafe {
+            enumerate_all(ptr::null_mut(), |count, devices| self.enumerate_physical_devices(instance, count, devices))
+        }
+    }
+
+    fn physical_device_groups(&self, instance: vk::Instance) -> Result<Vec<vulkan_sys::VkPhysicalDeviceGroupProperties>, vk::Result> {
+        unsafe {
+            let mut group: vulkan_sys::VkPhysicalDeviceGroupProperties = mem::zeroed();
+            group.sType = vulkan_sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_GROUP_PROPERTIES;
+            enumerate_all(group, |count, groups| self.enumerate_physical_device_groups(instance, &mut *count, groups))
+        }
+    }
 }
 
 trait PhysicalDevicePropertiesExt {
     fn get_name(&self) -> &ffi::CStr;
+    fn set_name(&mut self, name: &str);
+    fn apply_overrides(&mut self, overrides: &config::PropertyOverrides);
+    fn apply_filter(&mut self, filter: &config::Filter);
 }
 
 impl PhysicalDevicePropertiesExt for vulkan_sys::VkPhysicalDeviceProperties {
@@ -93,6 +266,61 @@ impl PhysicalDevicePropertiesExt for vulkan_sys::VkPhysicalDeviceProperties {
             ffi::CStr::from_ptr(self.deviceName.as_ptr())
         }
     }
+
+    fn set_name(&mut self, name: &str) {
+        // Leave room for the nul terminator, and truncate anything that won't fit
+        let len = name.len().min(self.deviceName.len() - 1);
+        for (dst, &src) in self.deviceName.iter_mut().zip(name.as_bytes()[..len].iter()) {
+            *dst = src as std::os::raw::c_char;
+        }
+        self.deviceName[len] = 0;
+    }
+
+    fn apply_overrides(&mut self, overrides: &config::PropertyOverrides) {
+        if let Some(name) = overrides.device_name.as_ref() {
+            self.set_name(name);
+        }
+        if let Some(vendor_id) = overrides.vendor_id {
+            self.vendorID = vendor_id;
+        }
+        if let Some(device_id) = overrides.device_id {
+            self.deviceID = device_id;
+        }
+        if let Some(driver_version) = overrides.driver_version {
+            self.driverVersion = driver_version;
+        }
+        if let Some(api_version) = overrides.api_version.as_ref().and_then(|v| parse_version(v)) {
+            self.apiVersion = api_version.into();
+        }
+    }
+
+    fn apply_filter(&mut self, filter: &config::Filter) {
+        if let Some(overrides) = filter.properties() {
+            self.apply_overrides(overrides);
+        }
+        if let Some(max_api_version) = filter.max_api_version().and_then(parse_version) {
+            let api_version = version::VulkanSemanticVersion::from_raw(self.apiVersion);
+            self.apiVersion = api_version.min(max_api_version).into();
+        }
+    }
+}
+
+fn parse_version(s: &str) -> Option<version::VulkanSemanticVersion> {
+    s.parse()
+        .map_err(|e| warn!("Invalid version in config: {}", e))
+        .ok()
+}
+
+trait ExtensionPropertiesExt {
+    fn get_name(&self) -> &ffi::CStr;
+}
+
+impl ExtensionPropertiesExt for vulkan_sys::VkExtensionProperties {
+    fn get_name(&self) -> &ffi::CStr {
+        unsafe {
+            ffi::CStr::from_ptr(self.extensionName.as_ptr())
+        }
+    }
 }
 
 trait PhysicalDeviceGroupPropertiesExt {
@@ -117,25 +345,219 @@ impl VkResultExt for vulkan_sys::VkResult {
     }
 }
 
-fn get_filter(instance: vk::Instance) -> Option<libc_regex_sys::Regex> {
-    use config::matches::InstanceMatch;
-    use libc_regex_sys::{
-        Regex,
-        RegcompFlags,
-        RegcompFlagsBuilder
+/// Iterates over an array of C strings, like `ppEnabledExtensionNames`
+unsafe fn c_str_array<'a>(strings: *const *const std::os::raw::c_char, count: u32) -> impl Iterator<Item=&'a ffi::CStr> {
+    let strings = if strings.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(strings, count as usize)
     };
-    let regex_flags: RegcompFlags = RegcompFlagsBuilder::default()
-        .extended(true)
-        .into();
-    let env_filter = env::var("VK_DEVICE_FILTER")
-        .ok()
-        .and_then(|ref s| Regex::new(s, regex_flags).ok());
-    if env_filter.is_some() {
-        return env_filter;
+    strings.iter()
+        .map(|&s| ffi::CStr::from_ptr(s))
+}
+
+/// Gets every value from a Vulkan enumeration function, asking again if the number of values grew
+/// between the call counting them and the call fetching them
+unsafe fn enumerate_all<T: Copy, F>(init: T, mut enumerate: F) -> Result<Vec<T>, vk::Result>
+where
+    F: FnMut(*mut u32, *mut T) -> vk::Result,
+{
+    loop {
+        let mut count = 0;
+        let status = enumerate(&mut count, ptr::null_mut());
+        if status != vulkan_sys::VkResult_VK_SUCCESS {
+            return Err(status);
+        }
+        let mut values: Vec<T> = iter::repeat(init)
+            .take(count as usize)
+            .collect();
+        match enumerate(&mut count, values.as_mut_ptr()) {
+            vulkan_sys::VkResult_VK_SUCCESS => {
+                values.truncate(count as usize);
+                return Ok(values);
+            },
+            vulkan_sys::VkResult_VK_INCOMPLETE => continue,
+            status => return Err(status),
+        }
+    }
+}
+
+/// Copies `values` out to an application-provided array with the usual two-call semantics: if
+/// `dst` is null only the count is written, otherwise at most `*count` values are copied, and
+/// `VK_INCOMPLETE` is returned if that wasn't all of them.
+unsafe fn write_enumeration<T: Copy>(values: &[T], count: *mut u32, dst: *mut T) -> vk::Result {
+    let count = count.as_mut().unwrap();
+    if dst.is_null() {
+        *count = values.len() as u32;
+        return vulkan_sys::VkResult_VK_SUCCESS;
     }
+    let written = values.len().min(*count as usize);
+    slice::from_raw_parts_mut(dst, written).copy_from_slice(&values[..written]);
+    *count = written as u32;
+    if written < values.len() {
+        vulkan_sys::VkResult_VK_INCOMPLETE
+    } else {
+        vulkan_sys::VkResult_VK_SUCCESS
+    }
+}
+
+/// Finds the first filter in the config whose match rule matches the given instance
+fn get_config_filter<H: VulkanHandle + Copy>(instance: H) -> Option<&'static config::Filter> {
+    use config::matches::InstanceMatch;
     Config::global().filters()
         .find(|f| f.match_rule().is_match(instance))
-        .and_then(|f| Regex::new(f.filter(), regex_flags).ok())
+}
+
+/// Checks `physical_device`, and any device group members chained on `create_info`, against the
+/// devices visible to the instance they belong to. Returns `false` if any of them would have been
+/// hidden.
+unsafe fn is_device_creation_allowed(physical_device: vulkan_sys::VkPhysicalDevice, create_info: &vulkan_sys::VkDeviceCreateInfo) -> bool {
+    let instance = dispatches::instance_handles().read().unwrap()
+        .get(&physical_device.vulkan_handle_key())
+        .map(|&instance| instance);
+    let instance = match instance {
+        Some(instance) => instance,
+        None => return true,
+    };
+    let dispatch = instance_dispatch(physical_device);
+    let visible = devices::visible_devices(instance, &dispatch, &dispatch.physical_devices(instance).unwrap_or_default());
+    let group_devices = (create_info.pNext as *const vk::VkStructHead).as_ref()
+        .into_iter()
+        .flat_map(|head| iter::once(head).chain(head.iter()))
+        .filter(|s| s.ty() == vulkan_sys::VkStructureType_VK_STRUCTURE_TYPE_DEVICE_GROUP_DEVICE_CREATE_INFO)
+        .map(|s| {
+            let info: &vulkan_sys::VkDeviceGroupDeviceCreateInfo = mem::transmute(s);
+            if info.pPhysicalDevices.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(info.pPhysicalDevices, info.physicalDeviceCount as usize)
+            }
+        })
+        .flat_map(|devices| devices.iter().map(|&d| d));
+    let excluded = iter::once(physical_device)
+        .chain(group_devices)
+        .find(|d| !visible.contains(d));
+    if let Some(excluded) = excluded {
+        let properties = dispatch.physical_device_properties(excluded);
+        warn!("DeviceFilterLayer: CreateDevice: refusing to create device on filtered physical device: {:?}", properties.get_name());
+        return false;
+    }
+    true
+}
+
+fn instance_dispatch<H: VulkanHandle>(handle: H) -> layer::DispatchTable {
+    let dispatches = dispatches::instances().read().unwrap();
+    let dispatch = dispatches.get(&handle.vulkan_handle_key()).map(Clone::clone);
+    mem::drop(dispatches);
+    dispatch.unwrap()
+}
+
+#[link_name = "DeviceFilterLayer_GetPhysicalDeviceProperties"]
+pub unsafe extern "C" fn get_physical_device_properties(
+    physical_device: vulkan_sys::VkPhysicalDevice,
+    properties: *mut vulkan_sys::VkPhysicalDeviceProperties
+) {
+    instance_dispatch(physical_device).get_physical_device_properties(physical_device, properties);
+    if let (Some(filter), Some(properties)) = (get_config_filter(physical_device), properties.as_mut()) {
+        properties.apply_filter(filter);
+    }
+}
+
+#[link_name = "DeviceFilterLayer_GetPhysicalDeviceProperties2"]
+pub unsafe extern "C" fn get_physical_device_properties2(
+    physical_device: vulkan_sys::VkPhysicalDevice,
+    properties: *mut vulkan_sys::VkPhysicalDeviceProperties2
+) {
+    instance_dispatch(physical_device).get_physical_device_properties2(physical_device, properties);
+    if let (Some(filter), Some(properties)) = (get_config_filter(physical_device), properties.as_mut()) {
+        properties.properties.apply_filter(filter);
+    }
+}
+
+#[link_name = "DeviceFilterLayer_GetPhysicalDeviceFeatures"]
+pub unsafe extern "C" fn get_physical_device_features(
+    physical_device: vulkan_sys::VkPhysicalDevice,
+    features: *mut vulkan_sys::VkPhysicalDeviceFeatures
+) {
+    instance_dispatch(physical_device).get_physical_device_features(physical_device, features);
+    let disabled = get_config_filter(physical_device)
+        .and_then(|f| f.disable_features());
+    if let (Some(disabled), Some(features)) = (disabled, features.as_mut()) {
+        features::disable_features(features, disabled);
+    }
+}
+
+#[link_name = "DeviceFilterLayer_GetPhysicalDeviceFeatures2"]
+pub unsafe extern "C" fn get_physical_device_features2(
+    physical_device: vulkan_sys::VkPhysicalDevice,
+    features: *mut vulkan_sys::VkPhysicalDeviceFeatures2
+) {
+    instance_dispatch(physical_device).get_physical_device_features2(physical_device, features);
+    let disabled = get_config_filter(physical_device)
+        .and_then(|f| f.disable_features());
+    if let (Some(disabled), Some(features)) = (disabled, features.as_mut()) {
+        features::disable_features2(features, disabled);
+    }
+}
+
+#[link_name = "DeviceFilterLayer_GetPhysicalDeviceMemoryProperties"]
+pub unsafe extern "C" fn get_physical_device_memory_properties(
+    physical_device: vulkan_sys::VkPhysicalDevice,
+    memory_properties: *mut vulkan_sys::VkPhysicalDeviceMemoryProperties
+) {
+    instance_dispatch(physical_device).get_physical_device_memory_properties(physical_device, memory_properties);
+    let max_size = get_config_filter(physical_device)
+        .and_then(|f| f.max_device_local_memory());
+    if let (Some(max_size), Some(memory_properties)) = (max_size, memory_properties.as_mut()) {
+        memory::clamp_heaps(memory_properties, max_size);
+    }
+}
+
+#[link_name = "DeviceFilterLayer_GetPhysicalDeviceMemoryProperties2"]
+pub unsafe extern "C" fn get_physical_device_memory_properties2(
+    physical_device: vulkan_sys::VkPhysicalDevice,
+    memory_properties: *mut vulkan_sys::VkPhysicalDeviceMemoryProperties2
+) {
+    instance_dispatch(physical_device).get_physical_device_memory_properties2(physical_device, memory_properties);
+    let max_size = get_config_filter(physical_device)
+        .and_then(|f| f.max_device_local_memory());
+    if let (Some(max_size), Some(memory_properties)) = (max_size, memory_properties.as_mut()) {
+        memory::clamp_heaps2(memory_properties, max_size);
+    }
+}
+
+#[link_name = "DeviceFilterLayer_GetPhysicalDeviceQueueFamilyProperties"]
+pub unsafe extern "C" fn get_physical_device_queue_family_properties(
+    physical_device: vulkan_sys::VkPhysicalDevice,
+    queue_family_property_count: *mut u32,
+    queue_family_properties: *mut vulkan_sys::VkQueueFamilyProperties
+) {
+    instance_dispatch(physical_device).get_physical_device_queue_family_properties(physical_device, queue_family_property_count, queue_family_properties);
+    if queue_family_properties.is_null() {
+        return;
+    }
+    if let Some(rules) = get_config_filter(physical_device).and_then(|f| f.queues()) {
+        slice::from_raw_parts_mut(queue_family_properties, *queue_family_property_count as usize)
+            .iter_mut()
+            .for_each(|properties| queues::apply_queue_rules(rules, properties));
+    }
+}
+
+#[link_name = "DeviceFilterLayer_GetPhysicalDeviceQueueFamilyProperties2"]
+pub unsafe extern "C" fn get_physical_device_queue_family_properties2(
+    physical_device: vulkan_sys::VkPhysicalDevice,
+    queue_family_property_count: *mut u32,
+    queue_family_properties: *mut vulkan_sys::VkQueueFamilyProperties2
+) {
+    instance_dispatch(physical_device).get_physical_device_queue_family_properties2(physical_device, queue_family_property_count, queue_family_properties);
+    if queue_family_properties.is_null() {
+        return;
+    }
+    if let Some(rules) = get_config_filter(physical_device).and_then(|f| f.queues()) {
+        slice::from_raw_parts_mut(queue_family_properties, *queue_family_property_count as usize)
+            .iter_mut()
+            .for_each(|properties| queues::apply_queue_rules(rules, &mut properties.queueFamilyProperties));
+    }
 }
 
 #[link_name = "DeviceGroupFilter_EnumeratePhysicalDeviceGroups"]
@@ -144,60 +566,22 @@ pub unsafe extern "C" fn enumerate_physical_device_groups(
     physical_device_group_count: *mut u32,
     physical_device_groups: *mut vulkan_sys::VkPhysicalDeviceGroupProperties
 ) -> vk::Result {
-    use std::collections::LinkedList;
-
-    let physical_device_group_count = physical_device_group_count.as_mut().unwrap();
-    let dispatch = {
-        let dispatches = dispatches::instances().read().unwrap();
-        let dispatch = dispatches.get(&instance.vulkan_handle_key()).map(Clone::clone);
-        mem::drop(dispatches);
-        dispatch.unwrap()
+    let dispatch = instance_dispatch(instance);
+    // Always work from the full list: filtering and selection look at every device, and the
+    // application's count from its first call is only the size of the filtered list
+    let groups = match dispatch.physical_device_groups(instance) {
+        Ok(groups) => groups,
+        Err(status) => return status,
     };
-    let status = dispatch.enumerate_physical_device_groups(instance, physical_device_group_count, physical_device_groups);
-    if !status.is_success_or_incomplete() {
-        return status;
-    }
-    // If devices is null, then we have to filter anyways to get the right # of potentially
-    // available devices, so we have to allocate our own array. We shouldn't return these devices
-    // anyways, as per
-    // https://www.khronos.org/registry/vulkan/specs/1.1-extensions/man/html/vkEnumeratePhysicalDevices.html
-    let mut buffer: Vec<vulkan_sys::VkPhysicalDeviceGroupProperties>;
-    let groups = if physical_device_groups.is_null() {
-        buffer = iter::repeat(mem::zeroed())
-            .take(*physical_device_group_count as usize)
-            .collect();
-        let status = dispatch.enumerate_physical_device_groups(instance, physical_device_group_count, buffer.as_mut_slice().as_mut_ptr());
-        if !status.is_success_or_incomplete() {
-            return status;
-        }
-        buffer.as_mut_slice()
-    } else {
-        slice::from_raw_parts_mut(physical_device_groups, *physical_device_group_count as usize)
-    };
-    if let Some(filter) = get_filter(instance) {
-        let group_matches = |group: &vulkan_sys::VkPhysicalDeviceGroupProperties| {
-            let filtered_count = group.physical_devices()
-                .iter()
-                .map(|&d| dispatch.physical_device_properties(d))
-                .filter(|p| p.get_name().to_str().as_ref().map(|s| filter.is_match(s)).unwrap_or(false))
-                .map(|p| {
-                    p
-                })
-                .count();
-            filtered_count == group.physical_devices().len()
-        };
-        let filtered_groups: LinkedList<vulkan_sys::VkPhysicalDeviceGroupProperties> = groups.iter()
-            .filter(move |&g| group_matches(g))
-            .map(|&g| g)
-            .collect();
-        *physical_device_group_count = filtered_groups.len() as u32;
-        for (i, g) in filtered_groups.iter().enumerate() {
-            groups[i] = *g;
-        }
-        status
-    } else {
-        status
+    let mut visible = devices::visible_groups(instance, &dispatch, &groups);
+    if let Some(count) = physical_device_group_count.as_ref().filter(|_| !physical_device_groups.is_null()) {
+        // Keep the structs the application chained on its own array
+        let dst = slice::from_raw_parts(physical_device_groups, (*count as usize).min(visible.len()));
+        visible.iter_mut()
+            .zip(dst)
+            .for_each(|(group, dst)| group.pNext = dst.pNext);
     }
+    write_enumeration(&visible, physical_device_group_count, physical_device_groups)
 }
 
 #[link_name = "DeviceFilterLayer_EnumeratePhysicalDevices"]
@@ -206,55 +590,20 @@ pub unsafe extern "C" fn enumerate_physical_devices(
     physical_device_count: *mut u32,
     physical_devices: *mut vulkan_sys::VkPhysicalDevice
 ) -> vk::Result {
-    use std::collections::LinkedList;
     let dispatch = {
         let dispatches = dispatches::instances().read().unwrap();
         let dispatch = dispatches.get(&instance.vulkan_handle_key()).map(Clone::clone);
         mem::drop(dispatches);
         dispatch.unwrap()
     };
-    let mut status = dispatch.enumerate_physical_devices(instance, physical_device_count, physical_devices);
-    if !status.is_success_or_incomplete() {
-        return status;
-    }
-
-    // If devices is null, then we have to filter anyways to get the right # of potentially
-    // available devices, so we have to allocate our own array. We shouldn't return these devices
-    // anyways, as per
-    // https://www.khronos.org/registry/vulkan/specs/1.1-extensions/man/html/vkEnumeratePhysicalDevices.html
-    let mut buffer: Vec<vulkan_sys::VkPhysicalDevice>;
-    let devices = if physical_devices.is_null() {
-        buffer = iter::repeat(mem::zeroed())
-            .take(*physical_device_count as usize)
-            .collect();
-        status = dispatch.enumerate_physical_devices(instance, physical_device_count, buffer.as_mut_slice().as_mut_ptr());
-        if !status.is_success_or_incomplete() {
-            return status;
-        }
-        buffer.as_mut_slice()
-    } else {
-        slice::from_raw_parts_mut(physical_devices, *physical_device_count as usize)
+    // Always work from the full list: filtering and selection look at every device, and the
+    // application's count from its first call is only the size of the filtered list
+    let devices = match dispatch.physical_devices(instance) {
+        Ok(devices) => devices,
+        Err(status) => return status,
     };
-
-    if let Some(filter) = get_filter(instance) {
-        let filtered_devices: LinkedList<vulkan_sys::VkPhysicalDevice> = devices.iter()
-            .map(|&device| (device, dispatch.physical_device_properties(device)))
-            .filter_map(|(device, ref properties)| if properties.get_name().to_str().as_ref().map(|s| filter.is_match(s)).unwrap_or(false) {
-                Some(device)
-            } else {
-                None
-            })
-            .collect();
-        let mut filtered_count = 0;
-        for (i, device) in filtered_devices.into_iter().enumerate() {
-            devices[i] = device;
-            filtered_count = i + 1;
-        }
-        *physical_device_count = filtered_count as u32;
-        status
-    } else {
-        status
-    }
+    let visible = devices::visible_devices(instance, &dispatch, &devices);
+    write_enumeration(&visible, physical_device_count, physical_devices)
 }
 
 #[allow(dead_code)]
@@ -354,6 +703,37 @@ pub unsafe extern "C" fn create_instance(
         let mut dispatches = dispatches::instances().write().unwrap();
         dispatches.insert((*instance).vulkan_handle_key(), dispatch_table);
     }
+    dispatches::instance_handles().write().unwrap().insert((*instance).vulkan_handle_key(), *instance);
+    {
+        let mut snapshot = dispatches::Snapshot::default();
+        let dispatch = instance_dispatch(*instance);
+        Config::global().visit_rules(&mut |rule| match rule {
+            config::MatchRule::Power { .. } if snapshot.power_source.is_none() => {
+                snapshot.power_source = Some(sysfs::power_source(Config::global().sysfs_root()));
+            },
+            config::MatchRule::PresentDevices { .. } if snapshot.present_devices.is_none() => {
+                snapshot.present_devices = Some(dispatch.physical_devices(*instance).unwrap_or_default().into_iter()
+                    .map(|physical_device| dispatch.physical_device_properties(physical_device).get_name().to_string_lossy().into_owned())
+                    .collect());
+            },
+            config::MatchRule::Schedule { .. } if snapshot.time.is_none() => {
+                snapshot.time = Some(schedule::now());
+            },
+            config::MatchRule::Ancestor { .. } if snapshot.ancestors.is_none() => {
+                snapshot.ancestors = Some(process::ancestors(Config::global().fs_root(), None));
+            },
+            config::MatchRule::Cwd { .. } if snapshot.cwd.is_none() => {
+                snapshot.cwd = env::current_dir().ok();
+            },
+            config::MatchRule::FileExists { path } if !snapshot.existing_files.contains_key(path) => {
+                if let Ok(cwd) = env::current_dir() {
+                    snapshot.existing_files.insert(path.clone(), process::file_exists(cwd, path));
+                }
+            },
+            _ => {},
+        });
+        dispatches::snapshots().write().unwrap().insert((*instance).vulkan_handle_key(), snapshot);
+    }
     {
         let application_info = create_info.pApplicationInfo.as_ref()
             .map(|info| vk::ApplicationInfo::from_sys(info));
@@ -362,6 +742,18 @@ pub unsafe extern "C" fn create_instance(
             dispatches.insert((*instance).vulkan_handle_key(), application_info);
         }
     }
+    {
+        let to_strings = |names: *const *const std::os::raw::c_char, count| {
+            c_str_array(names, count)
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect()
+        };
+        let enabled_names = dispatches::EnabledNames {
+            extensions: to_strings(create_info.ppEnabledExtensionNames, create_info.enabledExtensionCount),
+            layers: to_strings(create_info.ppEnabledLayerNames, create_info.enabledLayerCount),
+        };
+        dispatches::enabled_names().write().unwrap().insert((*instance).vulkan_handle_key(), enabled_names);
+    }
 
     //println!("DeviceFilterLayer: CreateInstance: done");
 
@@ -373,11 +765,20 @@ pub unsafe extern "C" fn destroy_instance(
     instance: vk::Instance,
     allocation_callbacks: *const vk::AllocationCallbacks
 ) {
+    // The instance is gone once the call returns, so its key has to be read first
+    let key = instance.vulkan_handle_key();
     let mut dispatches = dispatches::instances().write().unwrap();
-    if let Some(dispatch) = dispatches.get(&mem::transmute(instance)) {
+    if let Some(dispatch) = dispatches.get(&key) {
         dispatch.destroy_instance(instance, allocation_callbacks.as_ref());
     }
-    dispatches.remove(&mem::transmute(instance));
+    dispatches.remove(&key);
+
+    dispatches::instance_handles().write().unwrap().remove(&key);
+    dispatches::snapshots().write().unwrap().remove(&key);
+    dispatches::selected_devices().write().unwrap().remove(&key);
+    dispatches::device_infos().write().unwrap().remove(&key);
+    dispatches::application_infos().write().unwrap().remove(&key);
+    dispatches::enabled_names().write().unwrap().remove(&key);
 }
 
 #[link_name = "DeviceFilterLayer_DestroyDevice"]
@@ -385,11 +786,84 @@ pub unsafe extern "C" fn destroy_device(
     device: vulkan_sys::VkDevice,
     allocation_callbacks: *const vk::AllocationCallbacks
 ) {
+    // The device is gone once the call returns, so its key has to be read first
+    let key = device.vulkan_handle_key();
     let mut dispatches = dispatches::devices().write().unwrap();
-    if let Some(dispatch) = dispatches.get(&mem::transmute(device)) {
+    if let Some(dispatch) = dispatches.get(&key) {
         dispatch.destroy_device(device, allocation_callbacks.as_ref());
     }
-    dispatches.remove(&mem::transmute(device));
+    dispatches.remove(&key);
+
+    dispatches::memory_budgets().write().unwrap().remove(&key);
+}
+
+fn device_dispatch(device: vulkan_sys::VkDevice) -> layer::DeviceDispatchTable {
+    let dispatches = dispatches::devices().read().unwrap();
+    let dispatch = dispatches.get(&device.vulkan_handle_key()).map(Clone::clone);
+    mem::drop(dispatches);
+    dispatch.unwrap()
+}
+
+#[inline]
+fn has_memory_budget(device: vulkan_sys::VkDevice) -> bool {
+    dispatches::memory_budgets().read().unwrap().contains_key(&device.vulkan_handle_key())
+}
+
+#[link_name = "DeviceFilterLayer_AllocateMemory"]
+pub unsafe extern "C" fn allocate_memory(
+    device: vulkan_sys::VkDevice,
+    allocate_info: *const vulkan_sys::VkMemoryAllocateInfo,
+    allocation_callbacks: *const vk::AllocationCallbacks,
+    memory: *mut vulkan_sys::VkDeviceMemory
+) -> vk::Result {
+    use memory::Reservation;
+
+    let dispatch = device_dispatch(device);
+    let info = allocate_info.as_ref().unwrap();
+    let reservation = dispatches::memory_budgets().write().unwrap()
+        .get_mut(&device.vulkan_handle_key())
+        .map(|budget| budget.reserve(info.memoryTypeIndex, info.allocationSize))
+        .unwrap_or(Reservation::Untracked);
+    if reservation == Reservation::OverBudget {
+        warn!("DeviceFilterLayer: AllocateMemory: allocation of {} bytes exceeds device-local memory budget", info.allocationSize);
+        return vulkan_sys::VkResult_VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
+    let ret = dispatch.allocate_memory(device, allocate_info, allocation_callbacks, memory);
+    if let Reservation::Reserved(heap) = reservation {
+        let mut budgets = dispatches::memory_budgets().write().unwrap();
+        if let Some(budget) = budgets.get_mut(&device.vulkan_handle_key()) {
+            if ret == vulkan_sys::VkResult_VK_SUCCESS {
+                budget.track(*memory, heap, info.allocationSize);
+            } else {
+                budget.unreserve(heap, info.allocationSize);
+            }
+        }
+    }
+    ret
+}
+
+#[link_name = "DeviceFilterLayer_FreeMemory"]
+pub unsafe extern "C" fn free_memory(
+    device: vulkan_sys::VkDevice,
+    memory: vulkan_sys::VkDeviceMemory,
+    allocation_callbacks: *const vk::AllocationCallbacks
+) {
+    device_dispatch(device).free_memory(device, memory, allocation_callbacks);
+    if let Some(budget) = dispatches::memory_budgets().write().unwrap().get_mut(&device.vulkan_handle_key()) {
+        budget.free(memory);
+    }
+}
+
+/// Checks the queues requested in `create_info` against the queue families as they were reported
+/// to the application after applying `rules`.
+unsafe fn is_queue_creation_allowed(physical_device: vulkan_sys::VkPhysicalDevice, rules: &[config::QueueRule], create_info: &vulkan_sys::VkDeviceCreateInfo) -> bool {
+    let families = instance_dispatch(physical_device).queue_family_properties(physical_device);
+    let queue_create_infos = if create_info.pQueueCreateInfos.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(create_info.pQueueCreateInfos, create_info.queueCreateInfoCount as usize)
+    };
+    queues::is_queue_creation_allowed(rules, &families, queue_create_infos)
 }
 
 #[link_name = "DeviceFilterLayer_CreateDevice"]
@@ -402,6 +876,24 @@ pub unsafe extern "C" fn create_device(
     use layer::DeviceDispatchTable;
 
     let create_info = create_info.as_ref().unwrap();
+    if Config::global().enforce() && !is_device_creation_allowed(physical_device, create_info) {
+        return vulkan_sys::VkResult_VK_ERROR_INITIALIZATION_FAILED;
+    }
+    if let Some(filter) = get_config_filter(physical_device) {
+        use config::matches::ExtensionMatch;
+        let extension_patterns = filter.extension_patterns();
+        let hidden_extension = c_str_array(create_info.ppEnabledExtensionNames, create_info.enabledExtensionCount)
+            .find(|name| !extension_patterns.is_visible(&name.to_string_lossy()));
+        if let Some(name) = hidden_extension {
+            warn!("DeviceFilterLayer: CreateDevice: refusing to enable hidden extension: {:?}", name);
+            return vulkan_sys::VkResult_VK_ERROR_EXTENSION_NOT_PRESENT;
+        }
+        if let Some(rules) = filter.queues() {
+            if !is_queue_creation_allowed(physical_device, rules, create_info) {
+                return vulkan_sys::VkResult_VK_ERROR_INITIALIZATION_FAILED;
+            }
+        }
+    }
     let next: &mut vk::VkStructHead = mem::transmute(create_info.pNext);
     let layer_create_info = next
         .find_next(|s| {
@@ -433,6 +925,15 @@ pub unsafe extern "C" fn create_device(
     let dispatch_table = DeviceDispatchTable::load(gdpa, |name| gdpa.unwrap()(*device, name.as_ptr()));
     let mut dispatches = dispatches::devices().write().unwrap();
     dispatches.insert((*device).vulkan_handle_key(), dispatch_table);
+    mem::drop(dispatches);
+
+    let filter = get_config_filter(physical_device)
+        .filter(|f| f.limit_allocations());
+    if let Some(max_size) = filter.and_then(|f| f.max_device_local_memory()) {
+        let memory_properties = instance_dispatch(physical_device).physical_device_memory_properties(physical_device);
+        let mut budgets = dispatches::memory_budgets().write().unwrap();
+        budgets.insert((*device).vulkan_handle_key(), memory::MemoryBudget::new(&memory_properties, max_size));
+    }
 
     vulkan_sys::VkResult_VK_SUCCESS
 }
@@ -495,13 +996,36 @@ pub unsafe extern "C" fn enumerate_device_extension_properties(
     };
     let layer_name = layer_name.map(|s| s.to_str().expect("Invalid UTF8 layer name"));
     if layer_name.is_none() || layer_name.filter(|&n| is_device_filter_layer(n)).is_some() {
+        use config::matches::ExtensionMatch;
         let physical_device_handle: usize = mem::transmute(physical_device);
         if physical_device_handle == 0 {
             return vulkan_sys::VkResult_VK_SUCCESS;
         }
-        let dispatches = dispatches::instances().read().unwrap();
-        let dispatch = dispatches.get(&physical_device.vulkan_handle_key()).unwrap();
-        return dispatch.enumerate_device_extension_properties(physical_device, layer_name_orig, property_count, properties);
+        let dispatch = instance_dispatch(physical_device);
+        let filter = match get_config_filter(physical_device).filter(|f| f.filters_extensions()) {
+            Some(filter) => filter,
+            None => return dispatch.enumerate_device_extension_properties(physical_device, layer_name_orig, property_count, properties),
+        };
+        // We need the full list to know how many extensions survive filtering, regardless of how
+        // much room the application gave us
+        let mut count = 0;
+        let status = dispatch.enumerate_device_extension_properties(physical_device, layer_name_orig, &mut count, ptr::null_mut());
+        if status != vulkan_sys::VkResult_VK_SUCCESS {
+            return status;
+        }
+        let mut all_properties: Vec<vulkan_sys::VkExtensionProperties> = iter::repeat(mem::zeroed())
+            .take(count as usize)
+            .collect();
+        let status = dispatch.enumerate_device_extension_properties(physical_device, layer_name_orig, &mut count, all_properties.as_mut_ptr());
+        if !status.is_success_or_incomplete() {
+            return status;
+        }
+        all_properties.truncate(count as usize);
+        let extension_patterns = filter.extension_patterns();
+        let visible_properties: Vec<vulkan_sys::VkExtensionProperties> = all_properties.into_iter()
+            .filter(|p| extension_patterns.is_visible(&p.get_name().to_string_lossy()))
+            .collect();
+        return write_enumeration(&visible_properties, property_count, properties);
     }
     if !property_count.is_null() {
         *property_count = 0;
@@ -530,6 +1054,28 @@ pub unsafe extern "C" fn enumerate_instance_extension_properties(
     vulkan_sys::VkResult_VK_SUCCESS
 }
 
+/// Pre-instance hook for `vkEnumerateInstanceVersion`. The loader only calls this for implicit
+/// layers, since it's called before any instance (or list of explicit layers) exists.
+#[no_mangle]
+pub unsafe extern "C" fn DeviceFilterLayer_EnumerateInstanceVersion(
+    chain: *const vulkan_sys::VkEnumerateInstanceVersionChain,
+    api_version: *mut u32
+) -> vk::Result {
+    let chain = chain.as_ref().unwrap();
+    let ret = chain.pfnNextLayer.unwrap()(chain.pNextLink, api_version);
+    if ret != vulkan_sys::VkResult_VK_SUCCESS {
+        return ret;
+    }
+    let max_api_version = get_config_filter(NoInstance)
+        .and_then(|f| f.max_api_version())
+        .and_then(parse_version);
+    if let (Some(max_api_version), Some(api_version)) = (max_api_version, api_version.as_mut()) {
+        let real_version = version::VulkanSemanticVersion::from_raw(*api_version);
+        *api_version = real_version.min(max_api_version).into();
+    }
+    ret
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn DeviceFilterLayer_GetDeviceProcAddr(device: vulkan_sys::VkDevice, name: *const std::os::raw::c_char) -> vulkan_sys::PFN_vkVoidFunction {
     let n = ffi::CStr::from_ptr(name).to_str().unwrap();
@@ -555,6 +1101,15 @@ pub unsafe extern "C" fn DeviceFilterLayer_GetDeviceProcAddr(device: vulkan_sys:
             let pfn_destroy_device: vulkan_sys::PFN_vkDestroyDevice = Some(destroy_device);
             mem::transmute(pfn_destroy_device)
         },
+        // Allocations are only tracked on devices with a memory budget, others go straight down
+        "vkAllocateMemory" if has_memory_budget(device) => {
+            let pfn_allocate_memory: vulkan_sys::PFN_vkAllocateMemory = Some(allocate_memory);
+            mem::transmute(pfn_allocate_memory)
+        },
+        "vkFreeMemory" if has_memory_budget(device) => {
+            let pfn_free_memory: vulkan_sys::PFN_vkFreeMemory = Some(free_memory);
+            mem::transmute(pfn_free_memory)
+        },
         _ => {
 
             let dispatches = dispatches::devices().read().unwrap();
@@ -603,6 +1158,30 @@ mod lookup {
                 ["vkEnumeratePhysicalDeviceGroups", "vkEnumeratePhysicalDeviceGroupsKHR"].iter().for_each(|k| {
                     i_map.insert(k, mem::transmute(f));
                 });
+                let f: vulkan_sys::PFN_vkGetPhysicalDeviceProperties = Some(get_physical_device_properties);
+                i_map.insert("vkGetPhysicalDeviceProperties", mem::transmute(f));
+                let f: vulkan_sys::PFN_vkGetPhysicalDeviceProperties2 = Some(get_physical_device_properties2);
+                ["vkGetPhysicalDeviceProperties2", "vkGetPhysicalDeviceProperties2KHR"].iter().for_each(|k| {
+                    i_map.insert(k, mem::transmute(f));
+                });
+                let f: vulkan_sys::PFN_vkGetPhysicalDeviceFeatures = Some(get_physical_device_features);
+                i_map.insert("vkGetPhysicalDeviceFeatures", mem::transmute(f));
+                let f: vulkan_sys::PFN_vkGetPhysicalDeviceFeatures2 = Some(get_physical_device_features2);
+                ["vkGetPhysicalDeviceFeatures2", "vkGetPhysicalDeviceFeatures2KHR"].iter().for_each(|k| {
+                    i_map.insert(k, mem::transmute(f));
+                });
+                let f: vulkan_sys::PFN_vkGetPhysicalDeviceMemoryProperties = Some(get_physical_device_memory_properties);
+                i_map.insert("vkGetPhysicalDeviceMemoryProperties", mem::transmute(f));
+                let f: vulkan_sys::PFN_vkGetPhysicalDeviceMemoryProperties2 = Some(get_physical_device_memory_properties2);
+                ["vkGetPhysicalDeviceMemoryProperties2", "vkGetPhysicalDeviceMemoryProperties2KHR"].iter().for_each(|k| {
+                    i_map.insert(k, mem::transmute(f));
+                });
+                let f: vulkan_sys::PFN_vkGetPhysicalDeviceQueueFamilyProperties = Some(get_physical_device_queue_family_properties);
+                i_map.insert("vkGetPhysicalDeviceQueueFamilyProperties", mem::transmute(f));
+                let f: vulkan_sys::PFN_vkGetPhysicalDeviceQueueFamilyProperties2 = Some(get_physical_device_queue_family_properties2);
+                ["vkGetPhysicalDeviceQueueFamilyProperties2", "vkGetPhysicalDeviceQueueFamilyProperties2KHR"].iter().for_each(|k| {
+                    i_map.insert(k, mem::transmute(f));
+                });
                 let f: vulkan_sys::PFN_vkCreateDevice = Some(create_device);
                 i_map.insert("vkCreateDevice", mem::transmute(f));
                 let f: vulkan_sys::PFN_vkDestroyDevice = Some(destroy_device);
@@ -635,6 +1214,18 @@ pub trait VulkanHandle {
     fn vulkan_handle_key(self) -> usize;
 }
 
+/// Stands in for the instance in calls made before one exists, like `vkEnumerateInstanceVersion`.
+/// Nothing is ever recorded under its key, so only rules that don't depend on instance state can
+/// match it.
+#[derive(Clone, Copy)]
+struct NoInstance;
+
+impl VulkanHandle for NoInstance {
+    fn vulkan_handle_key(self) -> usize {
+        0
+    }
+}
+
 impl VulkanHandle for vk::Instance {
     fn vulkan_handle_key(self) -> usize {
         unsafe {
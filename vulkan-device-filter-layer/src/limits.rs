@@ -0,0 +1,125 @@
+use ::vulkan_sys as sys;
+
+macro_rules! limit_fields {
+    ($($field:ident),* $(,)?) => {
+        const FIELDS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+        /// Gets the value of a scalar field of `VkPhysicalDeviceLimits` by its Vulkan name.
+        /// Integer, size, flag and float fields are all widened to `f64` so they can be compared
+        /// against the same kind of value.
+        pub fn limit(limits: &sys::VkPhysicalDeviceLimits, name: &str) -> Option<f64> {
+            match name {
+                $(stringify!($field) => Some(limits.$field as f64),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+limit_fields! {
+    maxImageDimension1D,
+    maxImageDimension2D,
+    maxImageDimension3D,
+    maxImageDimensionCube,
+    maxImageArrayLayers,
+    maxTexelBufferElements,
+    maxUniformBufferRange,
+    maxStorageBufferRange,
+    maxPushConstantsSize,
+    maxMemoryAllocationCount,
+    maxSamplerAllocationCount,
+    bufferImageGranularity,
+    sparseAddressSpaceSize,
+    maxBoundDescriptorSets,
+    maxPerStageDescriptorSamplers,
+    maxPerStageDescriptorUniformBuffers,
+    maxPerStageDescriptorStorageBuffers,
+    maxPerStageDescriptorSampledImages,
+    maxPerStageDescriptorStorageImages,
+    maxPerStageDescriptorInputAttachments,
+    maxPerStageResources,
+    maxDescriptorSetSamplers,
+    maxDescriptorSetUniformBuffers,
+    maxDescriptorSetUniformBuffersDynamic,
+    maxDescriptorSetStorageBuffers,
+    maxDescriptorSetStorageBuffersDynamic,
+    maxDescriptorSetSampledImages,
+    maxDescriptorSetStorageImages,
+    maxDescriptorSetInputAttachments,
+    maxVertexInputAttributes,
+    maxVertexInputBindings,
+    maxVertexInputAttributeOffset,
+    maxVertexInputBindingStride,
+    maxVertexOutputComponents,
+    maxTessellationGenerationLevel,
+    maxTessellationPatchSize,
+    maxTessellationControlPerVertexInputComponents,
+    maxTessellationControlPerVertexOutputComponents,
+    maxTessellationControlPerPatchOutputComponents,
+    maxTessellationControlTotalOutputComponents,
+    maxTessellationEvaluationInputComponents,
+    maxTessellationEvaluationOutputComponents,
+    maxGeometryShaderInvocations,
+    maxGeometryInputComponents,
+    maxGeometryOutputComponents,
+    maxGeometryOutputVertices,
+    maxGeometryTotalOutputComponents,
+    maxFragmentInputComponents,
+    maxFragmentOutputAttachments,
+    maxFragmentDualSrcAttachments,
+    maxFragmentCombinedOutputResources,
+    maxComputeSharedMemorySize,
+    maxComputeWorkGroupInvocations,
+    subPixelPrecisionBits,
+    subTexelPrecisionBits,
+    mipmapPrecisionBits,
+    maxDrawIndexedIndexValue,
+    maxDrawIndirectCount,
+    maxSamplerLodBias,
+    maxSamplerAnisotropy,
+    maxViewports,
+    viewportSubPixelBits,
+    minMemoryMapAlignment,
+    minTexelBufferOffsetAlignment,
+    minUniformBufferOffsetAlignment,
+    minStorageBufferOffsetAlignment,
+    minTexelOffset,
+    maxTexelOffset,
+    minTexelGatherOffset,
+    maxTexelGatherOffset,
+    minInterpolationOffset,
+    maxInterpolationOffset,
+    subPixelInterpolationOffsetBits,
+    maxFramebufferWidth,
+    maxFramebufferHeight,
+    maxFramebufferLayers,
+    framebufferColorSampleCounts,
+    framebufferDepthSampleCounts,
+    framebufferStencilSampleCounts,
+    framebufferNoAttachmentsSampleCounts,
+    maxColorAttachments,
+    sampledImageColorSampleCounts,
+    sampledImageIntegerSampleCounts,
+    sampledImageDepthSampleCounts,
+    sampledImageStencilSampleCounts,
+    storageImageSampleCounts,
+    maxSampleMaskWords,
+    timestampComputeAndGraphics,
+    timestampPeriod,
+    maxClipDistances,
+    maxCullDistances,
+    maxCombinedClipAndCullDistances,
+    discreteQueuePriorities,
+    pointSizeGranularity,
+    lineWidthGranularity,
+    strictLines,
+    standardSampleLocations,
+    optimalBufferCopyOffsetAlignment,
+    optimalBufferCopyRowPitchAlignment,
+    nonCoherentAtomSize,
+}
+
+/// Returns `true` if `name` is a scalar field of `VkPhysicalDeviceLimits`
+pub fn is_known_limit(name: &str) -> bool {
+    FIELDS.contains(&name)
+}
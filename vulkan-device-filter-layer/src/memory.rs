@@ -0,0 +1,125 @@
+use ::vulkan_sys as sys;
+use crate::vk;
+use std::{
+    collections::BTreeMap,
+    mem,
+};
+
+#[inline(always)]
+fn is_device_local(heap: &sys::VkMemoryHeap) -> bool {
+    (heap.flags & sys::VkMemoryHeapFlagBits_VK_MEMORY_HEAP_DEVICE_LOCAL_BIT) != 0
+}
+
+/// Clamps the size of every device-local heap to `max_size`
+pub fn clamp_heaps(properties: &mut sys::VkPhysicalDeviceMemoryProperties, max_size: u64) {
+    let count = properties.memoryHeapCount as usize;
+    properties.memoryHeaps[..count].iter_mut()
+        .filter(|heap| is_device_local(heap))
+        .for_each(|heap| heap.size = heap.size.min(max_size));
+}
+
+/// Clamps the budget of every device-local heap to what's left of its (clamped) size in
+/// `properties` after the heap's current usage
+pub fn clamp_budget(budget: &mut sys::VkPhysicalDeviceMemoryBudgetPropertiesEXT, properties: &sys::VkPhysicalDeviceMemoryProperties) {
+    let count = properties.memoryHeapCount as usize;
+    properties.memoryHeaps[..count].iter()
+        .enumerate()
+        .filter(|&(_, heap)| is_device_local(heap))
+        .for_each(|(i, heap)| budget.heapBudget[i] = budget.heapBudget[i].min(heap.size.saturating_sub(budget.heapUsage[i])));
+}
+
+/// Like `clamp_heaps`, but also clamps a `VkPhysicalDeviceMemoryBudgetPropertiesEXT` chained on
+/// `properties`
+pub unsafe fn clamp_heaps2(properties: &mut sys::VkPhysicalDeviceMemoryProperties2, max_size: u64) {
+    clamp_heaps(&mut properties.memoryProperties, max_size);
+    let clamped = properties.memoryProperties;
+    let head: &mut vk::VkStructHead = mem::transmute(properties);
+    for s in head.iter_mut() {
+        if s.ty() == sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT {
+            let budget: &mut sys::VkPhysicalDeviceMemoryBudgetPropertiesEXT = mem::transmute(s);
+            clamp_budget(budget, &clamped);
+        }
+    }
+}
+
+/// Total size of all device-local heaps
+pub fn device_local_size(properties: &sys::VkPhysicalDeviceMemoryProperties) -> u64 {
+    properties.memoryHeaps[..properties.memoryHeapCount as usize].iter()
+        .filter(|heap| is_device_local(heap))
+        .map(|heap| heap.size)
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reservation {
+    /// The allocation isn't on a budgeted heap
+    Untracked,
+    /// The allocation fit in the budget of the given heap
+    Reserved(usize),
+    OverBudget,
+}
+
+/// Tracks device-local allocations made on a device against the (clamped) heap sizes that were
+/// reported to the application.
+pub struct MemoryBudget {
+    /// Budget for each heap, or `None` for heaps that aren't device-local
+    heap_limits: Vec<Option<u64>>,
+    heap_usage: Vec<u64>,
+    type_heaps: Vec<usize>,
+    allocations: BTreeMap<sys::VkDeviceMemory, (usize, u64)>,
+}
+
+impl MemoryBudget {
+    pub fn new(properties: &sys::VkPhysicalDeviceMemoryProperties, max_size: u64) -> Self {
+        let heaps = &properties.memoryHeaps[..properties.memoryHeapCount as usize];
+        let types = &properties.memoryTypes[..properties.memoryTypeCount as usize];
+        MemoryBudget {
+            heap_limits: heaps.iter()
+                .map(|heap| if is_device_local(heap) {
+                    Some(heap.size.min(max_size))
+                } else {
+                    None
+                })
+                .collect(),
+            heap_usage: vec![0; heaps.len()],
+            type_heaps: types.iter()
+                .map(|t| t.heapIndex as usize)
+                .collect(),
+            allocations: BTreeMap::new(),
+        }
+    }
+
+    /// Reserves `size` bytes on the heap backing `memory_type_index`
+    pub fn reserve(&mut self, memory_type_index: u32, size: u64) -> Reservation {
+        let heap = match self.type_heaps.get(memory_type_index as usize) {
+            Some(&heap) => heap,
+            None => return Reservation::Untracked,
+        };
+        let limit = match self.heap_limits.get(heap) {
+            Some(&Some(limit)) => limit,
+            _ => return Reservation::Untracked,
+        };
+        let usage = &mut self.heap_usage[heap];
+        if usage.saturating_add(size) > limit {
+            return Reservation::OverBudget;
+        }
+        *usage += size;
+        Reservation::Reserved(heap)
+    }
+
+    /// Returns a reservation whose allocation failed
+    pub fn unreserve(&mut self, heap: usize, size: u64) {
+        self.heap_usage[heap] = self.heap_usage[heap].saturating_sub(size);
+    }
+
+    /// Records a successful allocation for a previous reservation, so it can be released on free
+    pub fn track(&mut self, memory: sys::VkDeviceMemory, heap: usize, size: u64) {
+        self.allocations.insert(memory, (heap, size));
+    }
+
+    pub fn free(&mut self, memory: sys::VkDeviceMemory) {
+        if let Some((heap, size)) = self.allocations.remove(&memory) {
+            self.unreserve(heap, size);
+        }
+    }
+}
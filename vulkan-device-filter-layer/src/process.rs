@@ -0,0 +1,274 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    env,
+    ffi::CStr,
+    fs,
+    mem,
+    os::raw::c_char,
+    path::{
+        Path,
+        PathBuf,
+    },
+    ptr,
+};
+
+/// Looks up an entry with one of the reentrant `getpw*_r`/`getgr*_r` functions, growing the string
+/// buffer until the entry fits. The entry's strings point into that buffer, so they have to be
+/// copied out by `map`.
+unsafe fn lookup_entry<T, F, M, R>(f: F, map: M) -> Option<R>
+where
+    F: Fn(*mut T, *mut c_char, usize, *mut *mut T) -> libc::c_int,
+    M: FnOnce(&T) -> R,
+{
+    let mut buf: Vec<c_char> = vec![0; 1024];
+    loop {
+        let mut entry: T = mem::zeroed();
+        let mut result: *mut T = ptr::null_mut();
+        match f(&mut entry, buf.as_mut_ptr(), buf.len(), &mut result) {
+            libc::ERANGE if buf.len() < (1 << 20) => {
+                let len = buf.len() * 2;
+                buf.resize(len, 0);
+            },
+            0 if !result.is_null() => return Some(map(&entry)),
+            _ => return None,
+        }
+    }
+}
+
+/// The real user id of this process
+#[inline]
+pub fn uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+/// Name of the user with the given id, from the password database
+pub fn user_name(uid: u32) -> Option<String> {
+    unsafe {
+        lookup_entry(|pwd: *mut libc::passwd, buf, len, result| libc::getpwuid_r(uid, pwd, buf, len, result),
+                     |pwd| CStr::from_ptr(pwd.pw_name).to_string_lossy().into_owned())
+    }
+}
+
+/// The primary and supplementary group ids of this process
+pub fn gids() -> Vec<u32> {
+    let primary = unsafe { libc::getgid() };
+    let mut gids = vec![primary];
+    let count = unsafe { libc::getgroups(0, ptr::null_mut()) };
+    if count > 0 {
+        let mut groups: Vec<libc::gid_t> = vec![0; count as usize];
+        let count = unsafe { libc::getgroups(count, groups.as_mut_ptr()) };
+        groups.truncate(count.max(0) as usize);
+        gids.extend(groups.into_iter().filter(|&gid| gid != primary));
+    }
+    gids
+}
+
+/// Name of the group with the given id, from the group database
+pub fn group_name(gid: u32) -> Option<String> {
+    unsafe {
+        lookup_entry(|grp: *mut libc::group, buf, len, result| libc::getgrgid_r(gid, grp, buf, len, result),
+                     |grp| CStr::from_ptr(grp.gr_name).to_string_lossy().into_owned())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionType {
+    Wayland,
+    X11,
+    Tty,
+    /// Logged in over SSH, or running on a desktop started by xrdp or a VNC server
+    Remote,
+}
+
+/// Works out the kind of session this process runs in, using `var` to read the environment.
+///
+/// SSH sessions are remote, whatever display they forward, and so are desktops started by xrdp
+/// (which sets `XRDP_SESSION`) or by `vncserver` (which sets `VNCDESKTOP`). Otherwise
+/// `XDG_SESSION_TYPE` is used if it's set to something we know, falling back to whichever of
+/// `WAYLAND_DISPLAY` and `DISPLAY` is set, and finally to a text console. A local desktop that is
+/// only being shared, like with `x11vnc`, looks the same as any other.
+pub fn session_type_from<F>(var: F) -> SessionType
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let is_set = |name: &str| var(name).map(|v| !v.is_empty()).unwrap_or(false);
+    if is_set("SSH_CONNECTION") || is_set("XRDP_SESSION") || is_set("VNCDESKTOP") {
+        return SessionType::Remote;
+    }
+    match var("XDG_SESSION_TYPE").as_deref() {
+        Some("wayland") => return SessionType::Wayland,
+        Some("x11") => return SessionType::X11,
+        Some("tty") => return SessionType::Tty,
+        _ => {},
+    }
+    if is_set("WAYLAND_DISPLAY") {
+        SessionType::Wayland
+    } else if is_set("DISPLAY") {
+        SessionType::X11
+    } else {
+        SessionType::Tty
+    }
+}
+
+/// The kind of session this process runs in, from its environment
+#[inline]
+pub fn session_type() -> SessionType {
+    session_type_from(|name| env::var(name).ok())
+}
+
+/// The Flatpak application id, from the `name` key of the `[Application]` group of
+/// `root/.flatpak-info`, which only exists inside a Flatpak sandbox
+pub fn flatpak_app_id<P: AsRef<Path>>(root: P) -> Option<String> {
+    let info = fs::read_to_string(root.as_ref().join(".flatpak-info")).ok()?;
+    let mut in_application = false;
+    for line in info.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_application = line == "[Application]";
+        } else if in_application {
+            let mut parts = line.splitn(2, '=');
+            if parts.next().map(str::trim) == Some("name") {
+                return parts.next().map(|name| name.trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Paths of the cgroups this process belongs to, from `root/proc/self/cgroup`
+pub fn cgroups<P: AsRef<Path>>(root: P) -> Vec<String> {
+    fs::read_to_string(root.as_ref().join("proc/self/cgroup"))
+        .map(|contents| {
+            contents.lines()
+                .filter_map(|line| line.splitn(3, ':').nth(2))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Name of the snap this process was started from, from its cgroups. snapd puts every app in a
+/// systemd scope or service named `snap.<snap>.<app>...`.
+pub fn snap_name<P: AsRef<Path>>(root: P) -> Option<String> {
+    cgroups(root).iter()
+        .flat_map(|path| path.split('/'))
+        .filter_map(|unit| {
+            let mut parts = unit.splitn(3, '.');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("snap"), Some(name), Some(_)) if !name.is_empty() => Some(name.to_string()),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// A process, as seen in `/proc`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: i32,
+    /// Command name, truncated by the kernel to 15 bytes
+    pub comm: String,
+    /// Path of the executable, if we're allowed to see it
+    pub exe: Option<PathBuf>,
+}
+
+/// Reads the command name and parent pid from `/proc/<pid>/stat`. The command name is in
+/// parentheses and may contain anything, so it ends at the last `)`.
+fn read_stat(proc_dir: &Path) -> Option<(String, i32)> {
+    let stat = fs::read_to_string(proc_dir.join("stat")).ok()?;
+    let start = stat.find('(')?;
+    let end = stat.rfind(')')?;
+    let comm = stat.get(start + 1..end)?.to_string();
+    let ppid = stat[end + 1..].split_whitespace().nth(1)?.parse().ok()?;
+    Some((comm, ppid))
+}
+
+/// Lists the ancestors of this process from `root/proc`, starting with its parent and going up at
+/// most `max_depth` generations
+pub fn ancestors<P: AsRef<Path>>(root: P, max_depth: Option<usize>) -> Vec<ProcessInfo> {
+    let proc_root = root.as_ref().join("proc");
+    let mut ancestors: Vec<ProcessInfo> = Vec::new();
+    let mut pid = match read_stat(&proc_root.join("self")) {
+        Some((_, ppid)) => ppid,
+        None => return ancestors,
+    };
+    while pid > 0 && max_depth.map(|max_depth| ancestors.len() < max_depth).unwrap_or(true) {
+        // pid namespaces and races can make for odd trees, so make sure we don't go in circles
+        if ancestors.iter().any(|ancestor| ancestor.pid == pid) {
+            break;
+        }
+        let proc_dir = proc_root.join(pid.to_string());
+        let (comm, ppid) = match read_stat(&proc_dir) {
+            Some(stat) => stat,
+            None => break,
+        };
+        ancestors.push(ProcessInfo {
+            pid: pid,
+            comm: comm,
+            exe: fs::read_link(proc_dir.join("exe")).ok(),
+        });
+        pid = ppid;
+    }
+    ancestors
+}
+
+/// The host name of the machine: the node name from `uname`, or the contents of
+/// `root/etc/hostname` if that isn't set
+pub fn host_name<P: AsRef<Path>>(root: P) -> Option<String> {
+    let node_name = unsafe {
+        let mut name: libc::utsname = mem::zeroed();
+        if libc::uname(&mut name) == 0 {
+            Some(CStr::from_ptr(name.nodename.as_ptr()).to_string_lossy().into_owned())
+        } else {
+            None
+        }
+    };
+    node_name
+        .filter(|name| !name.is_empty() && name != "(none)")
+        .or_else(|| configured_host_name(root))
+}
+
+/// The host name configured in `root/etc/hostname`, ignoring comments
+pub fn configured_host_name<P: AsRef<Path>>(root: P) -> Option<String> {
+    fs::read_to_string(root.as_ref().join("etc/hostname")).ok()?
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+}
+
+/// Matches `name` against a shell-style wildcard `pattern`, where `*` matches any run of
+/// characters and `?` any single character
+fn is_wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            is_wildcard_match(&pattern[1..], name) || (!name.is_empty() && is_wildcard_match(pattern, &name[1..]))
+        },
+        (Some('?'), Some(_)) => is_wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => is_wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Returns `true` if `path` exists, relative to `dir` unless it's absolute. The last component
+/// of `path` may contain `*` and `?` wildcards, like `*.uproject`.
+pub fn file_exists<P: AsRef<Path>>(dir: P, path: &str) -> bool {
+    let path = dir.as_ref().join(path);
+    let file_name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(file_name) if file_name.contains(&['*', '?'][..]) => file_name,
+        _ => return path.exists(),
+    };
+    let pattern: Vec<char> = file_name.chars().collect();
+    let parent = path.parent().unwrap_or(Path::new("/"));
+    fs::read_dir(parent)
+        .map(|entries| {
+            entries.filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .any(|name| is_wildcard_match(&pattern, &name.chars().collect::<Vec<char>>()))
+        })
+        .unwrap_or(false)
+}
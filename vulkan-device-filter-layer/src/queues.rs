@@ -0,0 +1,75 @@
+use ::vulkan_sys as sys;
+use log::warn;
+
+pub use crate::config::{
+    QueueFlag,
+    QueueRule,
+};
+
+fn flag_bit(flag: QueueFlag) -> sys::VkQueueFlags {
+    match flag {
+        QueueFlag::Graphics => sys::VkQueueFlagBits_VK_QUEUE_GRAPHICS_BIT,
+        QueueFlag::Compute => sys::VkQueueFlagBits_VK_QUEUE_COMPUTE_BIT,
+        QueueFlag::Transfer => sys::VkQueueFlagBits_VK_QUEUE_TRANSFER_BIT,
+        QueueFlag::SparseBinding => sys::VkQueueFlagBits_VK_QUEUE_SPARSE_BINDING_BIT,
+        QueueFlag::Protected => sys::VkQueueFlagBits_VK_QUEUE_PROTECTED_BIT,
+        // VK_KHR_video_decode_queue and VK_KHR_video_encode_queue
+        QueueFlag::VideoDecode => 0x20,
+        QueueFlag::VideoEncode => 0x40,
+    }
+}
+
+fn flag_mask(flags: &[QueueFlag]) -> sys::VkQueueFlags {
+    flags.iter()
+        .fold(0, |mask, &flag| mask | flag_bit(flag))
+}
+
+fn is_rule_match(rule: &QueueRule, properties: &sys::VkQueueFamilyProperties) -> bool {
+    let with_flags = flag_mask(rule.with_flags());
+    let without_flags = flag_mask(rule.without_flags());
+    (properties.queueFlags & with_flags) == with_flags && (properties.queueFlags & without_flags) == 0
+}
+
+/// Applies all matching rules to the properties of a queue family.
+///
+/// Hidden families keep their index, so that indices the application got from us stay valid
+/// everywhere without translation, and their queue count. Only their queue flags are cleared, so
+/// nothing will select them.
+pub fn apply_queue_rules(rules: &[QueueRule], properties: &mut sys::VkQueueFamilyProperties) {
+    let real_properties = *properties;
+    for rule in rules.iter().filter(|rule| is_rule_match(rule, &real_properties)) {
+        if rule.hide() {
+            properties.queueFlags = 0;
+        }
+        if let Some(max_count) = rule.max_count() {
+            properties.queueCount = properties.queueCount.min(max_count);
+        }
+    }
+}
+
+/// Returns `true` if a family has been hidden by `apply_queue_rules`
+#[inline]
+pub fn is_hidden(properties: &sys::VkQueueFamilyProperties) -> bool {
+    properties.queueFlags == 0
+}
+
+/// Checks the queues requested by `queue_create_infos` against the queue families as they were
+/// reported to the application, after applying `rules` to the real `families`.
+pub fn is_queue_creation_allowed(rules: &[QueueRule], families: &[sys::VkQueueFamilyProperties], queue_create_infos: &[sys::VkDeviceQueueCreateInfo]) -> bool {
+    let mut families = families.to_vec();
+    families.iter_mut()
+        .for_each(|properties| apply_queue_rules(rules, properties));
+    queue_create_infos.iter().all(|info| {
+        match families.get(info.queueFamilyIndex as usize) {
+            Some(family) if is_hidden(family) => {
+                warn!("DeviceFilterLayer: CreateDevice: refusing to create queues in hidden family {}", info.queueFamilyIndex);
+                false
+            },
+            Some(family) if info.queueCount > family.queueCount => {
+                warn!("DeviceFilterLayer: CreateDevice: refusing to create {} queues in family {} (limited to {})", info.queueCount, info.queueFamilyIndex, family.queueCount);
+                false
+            },
+            _ => true,
+        }
+    })
+}
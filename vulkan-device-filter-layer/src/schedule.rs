@@ -0,0 +1,148 @@
+use serde::{
+    de,
+    Deserialize,
+    Deserializer,
+    Serialize,
+};
+use std::{
+    fmt,
+    mem,
+    str,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl Weekday {
+    /// Days since Sunday, like `tm_wday`
+    pub fn from_index(index: u32) -> Weekday {
+        match index % 7 {
+            0 => Weekday::Sunday,
+            1 => Weekday::Monday,
+            2 => Weekday::Tuesday,
+            3 => Weekday::Wednesday,
+            4 => Weekday::Thursday,
+            5 => Weekday::Friday,
+            _ => Weekday::Saturday,
+        }
+    }
+
+    #[inline]
+    pub fn previous(self) -> Weekday {
+        Weekday::from_index(self as u32 + 6)
+    }
+}
+
+/// A point in local time, to the minute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub weekday: Weekday,
+    /// Minutes since midnight
+    pub minute: u32,
+}
+
+/// The current time, in the local time zone
+pub fn now() -> LocalTime {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        LocalTime {
+            weekday: Weekday::from_index(tm.tm_wday as u32),
+            minute: (tm.tm_hour * 60 + tm.tm_min) as u32,
+        }
+    }
+}
+
+fn parse_time_of_day(s: &str) -> Option<u32> {
+    let mut parts = s.trim().splitn(2, ':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = parts.next()?.parse().ok()?;
+    match (hour, minute) {
+        (24, 0) => Some(24 * 60),
+        (hour, minute) if hour < 24 && minute < 60 => Some(hour * 60 + minute),
+        _ => None,
+    }
+}
+
+/// A range of times of day, written as `HH:MM-HH:MM`. The start is included and the end isn't. A
+/// range ending before it starts, like `22:00-06:00`, runs past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    start: u32,
+    end: u32,
+}
+
+impl TimeRange {
+    #[inline]
+    fn wraps(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+impl str::FromStr for TimeRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '-');
+        let mut next = || parts.next()
+            .and_then(parse_time_of_day)
+            .ok_or_else(|| format!("invalid time range (expected HH:MM-HH:MM): {:?}", s));
+        Ok(TimeRange {
+            start: next()?,
+            end: next()?,
+        })
+    }
+}
+
+impl fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}-{:02}:{:02}", self.start / 60, self.start % 60, self.end / 60, self.end % 60)
+    }
+}
+
+impl Serialize for TimeRange {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use de::Error;
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Returns `true` if `time` falls in one of `hours`, on one of `days`. A missing list allows
+/// any day or time. Ranges running past midnight belong to the day they start on, so `friday`
+/// with `22:00-06:00` includes early Saturday morning.
+pub fn is_scheduled(days: Option<&[Weekday]>, hours: Option<&[TimeRange]>, time: LocalTime) -> bool {
+    let is_day = |weekday: Weekday| days.map(|days| days.contains(&weekday)).unwrap_or(true);
+    match hours {
+        Some(hours) => hours.iter().any(|range| {
+            if !range.wraps() {
+                time.minute >= range.start && time.minute < range.end && is_day(time.weekday)
+            } else if time.minute >= range.start {
+                is_day(time.weekday)
+            } else {
+                time.minute < range.end && is_day(time.weekday.previous())
+            }
+        }),
+        None => is_day(time.weekday),
+    }
+}
@@ -0,0 +1,183 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    str,
+};
+
+/// A `major:minor` device number, as found in the `dev` attribute of a device node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeviceNumber {
+    pub major: i64,
+    pub minor: i64,
+}
+
+impl str::FromStr for DeviceNumber {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut parts = s.splitn(2, ':');
+        let mut next = || parts.next()
+            .and_then(|part| part.parse().ok())
+            .ok_or_else(|| format!("invalid device number: {:?}", s));
+        Ok(DeviceNumber {
+            major: next()?,
+            minor: next()?,
+        })
+    }
+}
+
+/// Reads a sysfs attribute, without the trailing newline
+pub fn read_attribute<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    fs::read_to_string(path)
+        .map(|s| s.trim_end().to_string())
+}
+
+/// A DRM card, with the device numbers of all of its nodes (primary and render)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrmCard {
+    pub name: String,
+    pub nodes: Vec<DeviceNumber>,
+    /// The firmware used this device for the boot console
+    pub boot_vga: bool,
+    /// At least one of the card's connectors has a display connected
+    pub connected: bool,
+}
+
+impl DrmCard {
+    #[inline]
+    pub fn has_node(&self, node: DeviceNumber) -> bool {
+        self.nodes.contains(&node)
+    }
+}
+
+fn is_card_name(name: &str) -> bool {
+    name.starts_with("card") && name.len() > 4 && name[4..].chars().all(|c| c.is_ascii_digit())
+}
+
+/// Lists the DRM cards under `root/class/drm`, where `root` is usually `/sys`
+pub fn drm_cards<P: AsRef<Path>>(root: P) -> io::Result<Vec<DrmCard>> {
+    let drm = root.as_ref().join("class/drm");
+    let mut names: Vec<String> = fs::read_dir(&drm)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    let cards = names.iter()
+        .filter(|name| is_card_name(name))
+        .map(|name| {
+            let card = drm.join(name);
+            let nodes = fs::read_dir(card.join("device/drm"))
+                .into_iter()
+                .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+                .filter_map(|entry| read_attribute(entry.path().join("dev")).ok())
+                .filter_map(|dev| dev.parse().ok())
+                .collect();
+            let boot_vga = read_attribute(card.join("device/boot_vga"))
+                .map(|v| v == "1")
+                .unwrap_or(false);
+            let connector_prefix = format!("{}-", name);
+            let connected = names.iter()
+                .filter(|connector| connector.starts_with(&connector_prefix))
+                .filter_map(|connector| read_attribute(drm.join(connector).join("status")).ok())
+                .any(|status| status == "connected");
+            DrmCard {
+                name: name.clone(),
+                nodes: nodes,
+                boot_vga: boot_vga,
+                connected: connected,
+            }
+        })
+        .collect();
+    Ok(cards)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+/// Works out what the system is running on from `root/class/power_supply`.
+///
+/// We're on battery if there's a system battery and no online non-battery supply (mains, USB-C,
+/// ...). Batteries of peripherals (with a `scope` of `Device`) are ignored, and machines without a
+/// system battery (most desktops, even with offline USB-C supplies) are on AC.
+pub fn power_source<P: AsRef<Path>>(root: P) -> PowerSource {
+    let supplies = match fs::read_dir(root.as_ref().join("class/power_supply")) {
+        Ok(entries) => entries,
+        Err(_) => return PowerSource::Ac,
+    };
+    let mut has_battery = false;
+    for supply in supplies.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let ty = read_attribute(supply.join("type")).unwrap_or_default();
+        if ty == "Battery" {
+            let scope = read_attribute(supply.join("scope")).unwrap_or_default();
+            has_battery = has_battery || scope != "Device";
+        } else if read_attribute(supply.join("online")).map(|v| v == "1").unwrap_or(false) {
+            return PowerSource::Ac;
+        }
+    }
+    if has_battery {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    }
+}
+
+/// Returns `true` if the PCI device at `address` (like `0000:08:00.0`) is external to the machine.
+/// That's the case if the kernel marked it or one of its parent bridges as `removable` (as it does
+/// for devices behind external-facing ports), or if one of its parent bridges is itself marked as
+/// `external_facing` by the firmware.
+pub fn is_external_pci_device<P: AsRef<Path>>(root: P, address: &str) -> bool {
+    let devices = root.as_ref().join("devices");
+    let device = match fs::canonicalize(root.as_ref().join("bus/pci/devices").join(address)) {
+        Ok(device) => device,
+        Err(_) => return false,
+    };
+    let devices = fs::canonicalize(&devices).unwrap_or(devices);
+    let is_set = |path: &Path, attribute: &str, value: &str| {
+        read_attribute(path.join(attribute))
+            .map(|v| v == value)
+            .unwrap_or(false)
+    };
+    let mut path: Option<PathBuf> = Some(device.clone());
+    while let Some(current) = path.filter(|p| p.starts_with(&devices) && p != &devices) {
+        if is_set(&current, "removable", "removable") {
+            return true;
+        }
+        // The external-facing port itself is part of the machine, only what's behind it isn't
+        if current != device && is_set(&current, "external_facing", "1") {
+            return true;
+        }
+        path = current.parent().map(Path::to_path_buf);
+    }
+    false
+}
+
+/// Load counters of a GPU, as exposed by amdgpu. Other drivers don't have them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GpuLoad {
+    pub busy_percent: Option<u64>,
+    pub vram_used: Option<u64>,
+}
+
+/// Reads the load counters of the PCI device at `address`
+pub fn gpu_load<P: AsRef<Path>>(root: P, address: &str) -> GpuLoad {
+    let device = root.as_ref().join("bus/pci/devices").join(address);
+    let counter = |name: &str| read_attribute(device.join(name)).ok()
+        .and_then(|v| v.parse().ok());
+    GpuLoad {
+        busy_percent: counter("gpu_busy_percent"),
+        vram_used: counter("mem_info_vram_used"),
+    }
+}
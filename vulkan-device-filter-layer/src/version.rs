@@ -1,6 +1,9 @@
-use std::fmt;
+use std::{
+    fmt,
+    str,
+};
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct VulkanSemanticVersion(u32);
 
 pub trait SemanticVersion {
@@ -91,3 +94,118 @@ impl fmt::Debug for VulkanSemanticVersion {
         write!(f, "VulkanSemanticVersion({}.{}.{})", self.major(), self.minor(), self.patch())
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError(String);
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid vulkan version: {:?}", &self.0)
+    }
+}
+
+/// Parses `major[.minor[.patch]]`, returning the lowest and highest versions it could stand for
+/// (`1.2` is anything from `1.2.0` to `1.2.4095`), and how many components were given.
+fn parse_partial(s: &str) -> Result<(VulkanSemanticVersion, VulkanSemanticVersion, usize), ParseVersionError> {
+    let err = || ParseVersionError(s.to_string());
+    let components = s.trim()
+        .split('.')
+        .map(|c| c.parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|_| err())?;
+    let (floor, ceiling) = match *components.as_slice() {
+        [major] => ((major, 0, 0), (major, 0x3ff, 0xfff)),
+        [major, minor] => ((major, minor, 0), (major, minor, 0xfff)),
+        [major, minor, patch] => ((major, minor, patch), (major, minor, patch)),
+        _ => return Err(err()),
+    };
+    let (major, minor, patch) = floor;
+    if major > 0x3ff || minor > 0x3ff || patch > 0xfff {
+        return Err(err());
+    }
+    let (ceiling_major, ceiling_minor, ceiling_patch) = ceiling;
+    Ok((
+        VulkanSemanticVersion::new(major, minor, patch),
+        VulkanSemanticVersion::new(ceiling_major, ceiling_minor, ceiling_patch),
+        components.len(),
+    ))
+}
+
+impl str::FromStr for VulkanSemanticVersion {
+    type Err = ParseVersionError;
+
+    /// Parses versions of the form `major[.minor[.patch]]`, with missing components being `0`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_partial(s).map(|(version, _, _)| version)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparator {
+    AtLeast(VulkanSemanticVersion),
+    AtMost(VulkanSemanticVersion),
+    Greater(VulkanSemanticVersion),
+    Less(VulkanSemanticVersion),
+}
+
+impl Comparator {
+    fn matches(&self, version: VulkanSemanticVersion) -> bool {
+        match *self {
+            Comparator::AtLeast(v) => version >= v,
+            Comparator::AtMost(v) => version <= v,
+            Comparator::Greater(v) => version > v,
+            Comparator::Less(v) => version < v,
+        }
+    }
+}
+
+/// A range of versions, written as comma-separated comparisons like `>=1.2, <1.3`.
+///
+/// Supported operators are `>=`, `>`, `<=`, `<`, `=` (or `==`), and `~`, which allows changes to
+/// the last component given (`~1.2` is `>=1.2, <1.3`, `~1` is `>=1, <2`). Partial versions stand
+/// for every version they're a prefix of, so `>1.2` is `>=1.3` and `=1.2` is `~1.2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq(Vec<Comparator>);
+
+impl VersionReq {
+    pub fn matches(&self, version: VulkanSemanticVersion) -> bool {
+        self.0.iter().all(|c| c.matches(version))
+    }
+}
+
+impl str::FromStr for VersionReq {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseVersionError(s.to_string());
+        let mut comparators = Vec::new();
+        for part in s.split(',').map(str::trim) {
+            let (op, version) = [">=", "<=", "==", ">", "<", "=", "~"].iter()
+                .find(|op| part.starts_with(*op))
+                .map(|op| (*op, &part[op.len()..]))
+                .ok_or_else(err)?;
+            let (floor, ceiling, components) = parse_partial(version).map_err(|_| err())?;
+            match op {
+                ">=" => comparators.push(Comparator::AtLeast(floor)),
+                ">" => comparators.push(Comparator::Greater(ceiling)),
+                "<=" => comparators.push(Comparator::AtMost(ceiling)),
+                "<" => comparators.push(Comparator::Less(floor)),
+                "=" | "==" => {
+                    comparators.push(Comparator::AtLeast(floor));
+                    comparators.push(Comparator::AtMost(ceiling));
+                },
+                "~" => {
+                    // Everything with the same components, except for the last one given
+                    let ceiling = match components {
+                        1 => ceiling,
+                        _ => VulkanSemanticVersion::new(floor.major(), floor.minor(), 0xfff),
+                    };
+                    comparators.push(Comparator::AtLeast(floor));
+                    comparators.push(Comparator::AtMost(ceiling));
+                },
+                _ => unreachable!(),
+            }
+        }
+        Ok(VersionReq(comparators))
+    }
+}
@@ -0,0 +1,25 @@
+use vulkan_device_filter_layer::balance;
+use std::{
+    env,
+    fs,
+    process,
+};
+
+#[test]
+fn round_robin_tickets() {
+    let dir = env::temp_dir().join(format!("vulkan-device-filter-balance-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let tickets: Vec<u64> = (0..3)
+        .map(|_| balance::next_ticket(&dir).unwrap())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(vec![0, 1, 2], tickets);
+}
+
+#[test]
+fn stable_hash_is_fnv1a() {
+    assert_eq!(0xcbf29ce484222325, balance::stable_hash(""));
+    assert_eq!(0xaf63dc4c8601ec8c, balance::stable_hash("a"));
+    assert_eq!(0x85944171f73967e8, balance::stable_hash("foobar"));
+}
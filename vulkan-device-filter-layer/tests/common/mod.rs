@@ -0,0 +1,40 @@
+use std::{
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process,
+};
+
+/// A scratch directory standing in for the root of a filesystem tree, like `/` or `/sys`,
+/// removed when dropped
+pub struct FakeRoot(pub PathBuf);
+
+impl FakeRoot {
+    pub fn new(name: &str) -> Self {
+        let root = env::temp_dir().join(format!("vulkan-device-filter-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        FakeRoot(root)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P, contents: &str) {
+        let path = self.0.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(&self, target: P, link: Q) {
+        let link = self.0.join(link);
+        fs::create_dir_all(link.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(target, link).unwrap();
+    }
+}
+
+impl Drop for FakeRoot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,427 @@
+//! Drives the layer's enumeration entry points against a fake driver, the way the loader would
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    env,
+    ffi::CStr,
+    fs,
+    mem,
+    os::raw::c_char,
+    ptr,
+    sync::{
+        Mutex,
+        Once,
+    },
+};
+use vulkan_device_filter_layer as layer;
+use vulkan_device_filter_layer::balance;
+use vulkan_sys as sys;
+
+const DEVICES: &[(&str, sys::VkPhysicalDeviceType)] = &[
+    ("llvmpipe (LLVM 15.0.7, 256 bits)", sys::VkPhysicalDeviceType_VK_PHYSICAL_DEVICE_TYPE_CPU),
+    ("AMD Radeon RX 7900 XTX", sys::VkPhysicalDeviceType_VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU),
+    ("NVIDIA GeForce RTX 4090", sys::VkPhysicalDeviceType_VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU),
+];
+
+/// Extensions every fake device supports
+const EXTENSIONS: &[&str] = &[
+    "VK_KHR_swapchain",
+    "VK_KHR_acceleration_structure",
+    "VK_KHR_ray_tracing_pipeline",
+    "VK_KHR_ray_query",
+];
+
+const CONFIG: &str = "
+filters:
+  - filter: ^AMD
+    match_rule:
+      And:
+        rules:
+          - AppInfo:
+              name: ^windowed$
+          - InstanceExtensions:
+              all: [VK_KHR_surface, VK_KHR_xcb_surface]
+  - filter: ^NVIDIA
+    match_rule:
+      And:
+        rules:
+          - AppInfo:
+              name: ^windowed$
+          - InstanceExtensions:
+              any: [VK_KHR_surface, VK_KHR_display]
+  - match_rule:
+      AppInfo:
+        name: ^extensions$
+    hide_extensions:
+      - ^VK_KHR_ray_query$
+    only_extensions:
+      - ^VK_KHR_swapchain$
+      - ^VK_KHR_ray_
+
+  - match_rule:
+      AppInfo:
+        name: ^hash$
+    select:
+      hash:
+        env: ENUMERATE_TEST_JOB
+  - match_rule:
+      AppInfo:
+        name: ^cpu$
+    hide_cpu_devices: true
+";
+
+/// Physical device handles of each fake instance, by dispatch key. Like real dispatchable handles,
+/// each points to its dispatch key, followed here by the index of the device in `DEVICES`. The key
+/// is the address of a separate allocation, like the loader's dispatch table.
+static mut FAKE_DEVICES: Option<Mutex<HashMap<usize, Vec<usize>>>> = None;
+/// How many times the features of each physical device were queried
+static mut FEATURE_QUERIES: Option<Mutex<HashMap<usize, usize>>> = None;
+static INIT: Once = Once::new();
+
+thread_local! {
+    /// Dispatch key of a destroyed instance for the next instance created on this thread to take
+    /// over, along with its physical device handles, the way freed memory gets reused
+    static REUSED_KEY: Cell<Option<usize>> = Cell::new(None);
+}
+
+fn init() {
+    INIT.call_once(|| {
+        let config = env::temp_dir().join(format!("vulkan-device-filter-enumerate-{}.yml", std::process::id()));
+        fs::write(&config, CONFIG).unwrap();
+        env::set_var("VK_DEVICE_FILTER_CONFIG", &config);
+        unsafe {
+            FAKE_DEVICES = Some(Mutex::new(HashMap::new()));
+            FEATURE_QUERIES = Some(Mutex::new(HashMap::new()));
+        }
+    });
+}
+
+fn fake_devices() -> &'static Mutex<HashMap<usize, Vec<usize>>> {
+    unsafe { FAKE_DEVICES.as_ref().unwrap() }
+}
+
+fn feature_queries() -> &'static Mutex<HashMap<usize, usize>> {
+    unsafe { FEATURE_QUERIES.as_ref().unwrap() }
+}
+
+unsafe fn device_index(physical_device: sys::VkPhysicalDevice) -> usize {
+    *(physical_device as *const usize).offset(1)
+}
+
+unsafe extern "C" fn fake_create_instance(
+    _create_info: *const sys::VkInstanceCreateInfo,
+    _allocation_callbacks: *const sys::VkAllocationCallbacks,
+    instance: *mut sys::VkInstance
+) -> sys::VkResult {
+    let key = match REUSED_KEY.with(Cell::take) {
+        Some(key) => key,
+        None => {
+            let key = Box::into_raw(Box::new(0usize)) as usize;
+            let devices = (0..DEVICES.len())
+                .map(|index| Box::into_raw(Box::new([key, index])) as usize)
+                .collect();
+            fake_devices().lock().unwrap().insert(key, devices);
+            key
+        },
+    };
+    *instance = Box::into_raw(Box::new(key)) as sys::VkInstance;
+    sys::VkResult_VK_SUCCESS
+}
+
+/// Leaves the handle allocated, but clears its dispatch key, so that anything still reading it
+/// afterwards gets the wrong key
+unsafe extern "C" fn fake_destroy_instance(instance: sys::VkInstance, _allocation_callbacks: *const sys::VkAllocationCallbacks) {
+    *(instance as *mut usize) = 0;
+}
+
+unsafe extern "C" fn fake_enumerate_physical_devices(
+    instance: sys::VkInstance,
+    count: *mut u32,
+    physical_devices: *mut sys::VkPhysicalDevice
+) -> sys::VkResult {
+    let devices = fake_devices().lock().unwrap()[&*(instance as *const usize)].clone();
+    if physical_devices.is_null() {
+        *count = devices.len() as u32;
+        return sys::VkResult_VK_SUCCESS;
+    }
+    let written = devices.len().min(*count as usize);
+    for (i, &device) in devices[..written].iter().enumerate() {
+        *physical_devices.add(i) = device as sys::VkPhysicalDevice;
+    }
+    *count = written as u32;
+    if written < devices.len() {
+        sys::VkResult_VK_INCOMPLETE
+    } else {
+        sys::VkResult_VK_SUCCESS
+    }
+}
+
+unsafe extern "C" fn fake_enumerate_physical_device_groups(
+    instance: sys::VkInstance,
+    count: *mut u32,
+    groups: *mut sys::VkPhysicalDeviceGroupProperties
+) -> sys::VkResult {
+    let devices = fake_devices().lock().unwrap()[&*(instance as *const usize)].clone();
+    if groups.is_null() {
+        *count = devices.len() as u32;
+        return sys::VkResult_VK_SUCCESS;
+    }
+    let written = devices.len().min(*count as usize);
+    for (i, &device) in devices[..written].iter().enumerate() {
+        let group = &mut *groups.add(i);
+        group.physicalDeviceCount = 1;
+        group.physicalDevices[0] = device as sys::VkPhysicalDevice;
+        group.subsetAllocation = 0;
+    }
+    *count = written as u32;
+    if written < devices.len() {
+        sys::VkResult_VK_INCOMPLETE
+    } else {
+        sys::VkResult_VK_SUCCESS
+    }
+}
+
+unsafe extern "C" fn fake_get_physical_device_properties(
+    physical_device: sys::VkPhysicalDevice,
+    properties: *mut sys::VkPhysicalDeviceProperties
+) {
+    let (name, device_type) = DEVICES[device_index(physical_device)];
+    let properties = &mut *properties;
+    *properties = mem::zeroed();
+    properties.apiVersion = layer::version::VulkanSemanticVersion::new(1, 0, 0).into();
+    properties.deviceType = device_type;
+    for (dst, &b) in properties.deviceName.iter_mut().zip(name.as_bytes()) {
+        *dst = b as c_char;
+    }
+}
+
+unsafe extern "C" fn fake_get_physical_device_features(physical_device: sys::VkPhysicalDevice, features: *mut sys::VkPhysicalDeviceFeatures) {
+    *feature_queries().lock().unwrap().entry(physical_device as usize).or_insert(0) += 1;
+    *features = mem::zeroed();
+}
+
+unsafe extern "C" fn fake_get_physical_device_memory_properties(_physical_device: sys::VkPhysicalDevice, properties: *mut sys::VkPhysicalDeviceMemoryProperties) {
+    *properties = mem::zeroed();
+}
+
+unsafe extern "C" fn fake_get_physical_device_queue_family_properties(_physical_device: sys::VkPhysicalDevice, count: *mut u32, _properties: *mut sys::VkQueueFamilyProperties) {
+    *count = 0;
+}
+
+unsafe extern "C" fn fake_enumerate_device_extension_properties(
+    _physical_device: sys::VkPhysicalDevice,
+    _layer_name: *const c_char,
+    count: *mut u32,
+    properties: *mut sys::VkExtensionProperties
+) -> sys::VkResult {
+    if properties.is_null() {
+        *count = EXTENSIONS.len() as u32;
+        return sys::VkResult_VK_SUCCESS;
+    }
+    let written = EXTENSIONS.len().min(*count as usize);
+    for (i, name) in EXTENSIONS[..written].iter().enumerate() {
+        let extension = &mut *properties.add(i);
+        *extension = mem::zeroed();
+        for (dst, &b) in extension.extensionName.iter_mut().zip(name.as_bytes()) {
+            *dst = b as c_char;
+        }
+    }
+    *count = written as u32;
+    if written < EXTENSIONS.len() {
+        sys::VkResult_VK_INCOMPLETE
+    } else {
+        sys::VkResult_VK_SUCCESS
+    }
+}
+
+unsafe extern "C" fn fake_get_instance_proc_addr(_instance: sys::VkInstance, name: *const c_char) -> sys::PFN_vkVoidFunction {
+    let f: *const () = match CStr::from_ptr(name).to_bytes() {
+        b"vkCreateInstance" => fake_create_instance as *const (),
+        b"vkDestroyInstance" => fake_destroy_instance as *const (),
+        b"vkEnumeratePhysicalDevices" => fake_enumerate_physical_devices as *const (),
+        b"vkEnumeratePhysicalDeviceGroups" => fake_enumerate_physical_device_groups as *const (),
+        b"vkGetPhysicalDeviceProperties" => fake_get_physical_device_properties as *const (),
+        b"vkGetPhysicalDeviceFeatures" => fake_get_physical_device_features as *const (),
+        b"vkGetPhysicalDeviceMemoryProperties" => fake_get_physical_device_memory_properties as *const (),
+        b"vkGetPhysicalDeviceQueueFamilyProperties" => fake_get_physical_device_queue_family_properties as *const (),
+        b"vkEnumerateDeviceExtensionProperties" => fake_enumerate_device_extension_properties as *const (),
+        _ => return None,
+    };
+    Some(mem::transmute(f))
+}
+
+/// Creates an instance through the layer, on top of the fake driver
+unsafe fn create_instance(application_name: &CStr) -> sys::VkInstance {
+    create_instance_with_extensions(application_name, &[])
+}
+
+unsafe fn create_instance_with_extensions(application_name: &CStr, extensions: &[&CStr]) -> sys::VkInstance {
+    init();
+    let mut link: sys::VkLayerInstanceLink = mem::zeroed();
+    link.pfnNextGetInstanceProcAddr = Some(fake_get_instance_proc_addr);
+    let mut layer_info: sys::VkLayerInstanceCreateInfo = mem::zeroed();
+    layer_info.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_LOADER_INSTANCE_CREATE_INFO;
+    layer_info.function = sys::VkLayerFunction__VK_LAYER_LINK_INFO;
+    layer_info.u.pLayerInfo = &mut link;
+    let mut application_info: sys::VkApplicationInfo = mem::zeroed();
+    application_info.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_APPLICATION_INFO;
+    application_info.pApplicationName = application_name.as_ptr();
+    let mut create_info: sys::VkInstanceCreateInfo = mem::zeroed();
+    create_info.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_INSTANCE_CREATE_INFO;
+    create_info.pNext = &layer_info as *const _ as *const _;
+    create_info.pApplicationInfo = &application_info;
+    let extensions: Vec<*const c_char> = extensions.iter()
+        .map(|name| name.as_ptr())
+        .collect();
+    create_info.enabledExtensionCount = extensions.len() as u32;
+    create_info.ppEnabledExtensionNames = extensions.as_ptr();
+    let mut instance: sys::VkInstance = ptr::null_mut();
+    assert_eq!(sys::VkResult_VK_SUCCESS, layer::create_instance(&create_info, ptr::null(), &mut instance));
+    instance
+}
+
+/// Enumerates like an application: once for the count, then again with an array of that size
+unsafe fn enumerate_devices(instance: sys::VkInstance) -> Vec<sys::VkPhysicalDevice> {
+    let mut count = 0;
+    assert_eq!(sys::VkResult_VK_SUCCESS, layer::enumerate_physical_devices(instance, &mut count, ptr::null_mut()));
+    let mut devices = vec![ptr::null_mut(); count as usize];
+    assert_eq!(sys::VkResult_VK_SUCCESS, layer::enumerate_physical_devices(instance, &mut count, devices.as_mut_ptr()));
+    assert_eq!(devices.len(), count as usize);
+    devices
+}
+
+unsafe fn device_names(devices: &[sys::VkPhysicalDevice]) -> Vec<&'static str> {
+    devices.iter()
+        .map(|&device| DEVICES[device_index(device)].0)
+        .collect()
+}
+
+#[test]
+fn hidden_cpu_devices_dont_displace_gpus() {
+    unsafe {
+        let instance = create_instance(CStr::from_bytes_with_nul(b"cpu\0").unwrap());
+        let devices = enumerate_devices(instance);
+        assert_eq!(vec![DEVICES[1].0, DEVICES[2].0], device_names(&devices));
+
+        let mut count = 0;
+        assert_eq!(sys::VkResult_VK_SUCCESS, layer::enumerate_physical_device_groups(instance, &mut count, ptr::null_mut()));
+        assert_eq!(2, count);
+        let mut groups: Vec<sys::VkPhysicalDeviceGroupProperties> = vec![mem::zeroed(); count as usize];
+        assert_eq!(sys::VkResult_VK_SUCCESS, layer::enumerate_physical_device_groups(instance, &mut count, groups.as_mut_ptr()));
+        let members: Vec<sys::VkPhysicalDevice> = groups.iter()
+            .map(|group| group.physicalDevices[0])
+            .collect();
+        assert_eq!(vec![DEVICES[1].0, DEVICES[2].0], device_names(&members));
+
+        // Asking for fewer devices than there are still gets the first visible ones
+        let mut count = 1;
+        let mut device = ptr::null_mut();
+        assert_eq!(sys::VkResult_VK_INCOMPLETE, layer::enumerate_physical_devices(instance, &mut count, &mut device));
+        assert_eq!(vec![DEVICES[1].0], device_names(&[device]));
+    }
+}
+
+#[test]
+fn hash_selection_sees_every_device() {
+    // Pick a job that doesn't hash to the first device, which is all a truncated list would hold
+    let job = (0..)
+        .map(|i| format!("job-{}", i))
+        .find(|job| balance::stable_hash(job) % DEVICES.len() as u64 == 2)
+        .unwrap();
+    env::set_var("ENUMERATE_TEST_JOB", &job);
+    unsafe {
+        let instance = create_instance(CStr::from_bytes_with_nul(b"hash\0").unwrap());
+        let devices = enumerate_devices(instance);
+        assert_eq!(vec![DEVICES[2].0], device_names(&devices));
+        // The choice is stable across calls
+        assert_eq!(devices, enumerate_devices(instance));
+    }
+}
+
+#[test]
+fn devices_are_queried_once() {
+    unsafe {
+        let instance = create_instance(CStr::from_bytes_with_nul(b"cpu\0").unwrap());
+        enumerate_devices(instance);
+        enumerate_devices(instance);
+        let devices = fake_devices().lock().unwrap()[&*(instance as *const usize)].clone();
+        let queries = feature_queries().lock().unwrap().clone();
+        for device in devices {
+            assert_eq!(Some(&1), queries.get(&device));
+        }
+    }
+}
+
+#[test]
+fn destroyed_instances_forget_their_devices() {
+    unsafe {
+        let instance = create_instance(CStr::from_bytes_with_nul(b"cpu\0").unwrap());
+        let key = *(instance as *const usize);
+        enumerate_devices(instance);
+        layer::destroy_instance(instance, ptr::null());
+        assert_eq!(0, *(instance as *const usize));
+
+        // A new instance under the same key and with the same devices has to query them again
+        REUSED_KEY.with(|reused| reused.set(Some(key)));
+        let instance = create_instance(CStr::from_bytes_with_nul(b"cpu\0").unwrap());
+        assert_eq!(key, *(instance as *const usize));
+        enumerate_devices(instance);
+        let devices = fake_devices().lock().unwrap()[&key].clone();
+        let queries = feature_queries().lock().unwrap().clone();
+        for device in devices {
+            assert_eq!(Some(&2), queries.get(&device));
+        }
+    }
+}
+
+unsafe fn extension_names(properties: &[sys::VkExtensionProperties]) -> Vec<String> {
+    properties.iter()
+        .map(|extension| CStr::from_ptr(extension.extensionName.as_ptr()).to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn extensions_are_hidden_before_only_extensions() {
+    unsafe {
+        let instance = create_instance(CStr::from_bytes_with_nul(b"extensions\0").unwrap());
+        let device = enumerate_devices(instance)[0];
+
+        let mut count = 0;
+        assert_eq!(sys::VkResult_VK_SUCCESS, layer::enumerate_device_extension_properties(device, ptr::null(), &mut count, ptr::null_mut()));
+        assert_eq!(2, count);
+        let mut properties: Vec<sys::VkExtensionProperties> = vec![mem::zeroed(); count as usize];
+        assert_eq!(sys::VkResult_VK_SUCCESS, layer::enumerate_device_extension_properties(device, ptr::null(), &mut count, properties.as_mut_ptr()));
+        assert_eq!(vec!["VK_KHR_swapchain", "VK_KHR_ray_tracing_pipeline"], extension_names(&properties));
+
+        // Less room than there are visible extensions still gets the first ones
+        let mut count = 1;
+        let mut properties: Vec<sys::VkExtensionProperties> = vec![mem::zeroed(); 1];
+        assert_eq!(sys::VkResult_VK_INCOMPLETE, layer::enumerate_device_extension_properties(device, ptr::null(), &mut count, properties.as_mut_ptr()));
+        assert_eq!(1, count);
+        assert_eq!(vec!["VK_KHR_swapchain"], extension_names(&properties));
+    }
+}
+
+#[test]
+fn instance_extensions_pick_filters() {
+    let name = |s: &'static [u8]| CStr::from_bytes_with_nul(s).unwrap();
+    let windowed = name(b"windowed\0");
+    let surface = name(b"VK_KHR_surface\0");
+    let xcb_surface = name(b"VK_KHR_xcb_surface\0");
+    unsafe {
+        // Both extensions are enabled, so `all` matches first
+        let instance = create_instance_with_extensions(windowed, &[surface, xcb_surface]);
+        assert_eq!(vec![DEVICES[1].0], device_names(&enumerate_devices(instance)));
+
+        // Only one of them is, which is enough for `any`
+        let instance = create_instance_with_extensions(windowed, &[surface]);
+        assert_eq!(vec![DEVICES[2].0], device_names(&enumerate_devices(instance)));
+
+        // Neither filter matches without the extensions
+        let instance = create_instance_with_extensions(windowed, &[xcb_surface]);
+        assert_eq!(DEVICES.len(), enumerate_devices(instance).len());
+        let instance = create_instance(windowed);
+        assert_eq!(DEVICES.len(), enumerate_devices(instance).len());
+    }
+}
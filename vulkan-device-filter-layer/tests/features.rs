@@ -0,0 +1,64 @@
+use std::{
+    mem,
+    ptr,
+};
+use vulkan_device_filter_layer::features::{
+    self,
+    FeatureName,
+};
+use vulkan_sys as sys;
+
+const VK_TRUE: sys::VkBool32 = 1;
+
+#[test]
+fn unknown_feature_names_are_rejected() {
+    let names: Vec<FeatureName> = serde_yaml::from_str("[geometryShader, multiview, shaderInt8, runtimeDescriptorArray]").unwrap();
+    let names: Vec<&str> = names.iter().map(AsRef::as_ref).collect();
+    assert_eq!(vec!["geometryShader", "multiview", "shaderInt8", "runtimeDescriptorArray"], names);
+
+    assert!(serde_yaml::from_str::<FeatureName>("geometryShaders").is_err());
+    assert!(serde_yaml::from_str::<FeatureName>("GeometryShader").is_err());
+    assert!(serde_yaml::from_str::<Vec<FeatureName>>("[geometryShader, rayTracingPipeline]").is_err());
+}
+
+#[test]
+fn disable_features_in_chained_structs() {
+    unsafe {
+        let mut descriptor_indexing: sys::VkPhysicalDeviceDescriptorIndexingFeatures = mem::zeroed();
+        descriptor_indexing.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_DESCRIPTOR_INDEXING_FEATURES;
+        descriptor_indexing.runtimeDescriptorArray = VK_TRUE;
+        descriptor_indexing.descriptorBindingPartiallyBound = VK_TRUE;
+        let mut vulkan12: sys::VkPhysicalDeviceVulkan12Features = mem::zeroed();
+        vulkan12.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_2_FEATURES;
+        vulkan12.pNext = &mut descriptor_indexing as *mut _ as *mut _;
+        vulkan12.shaderInt8 = VK_TRUE;
+        vulkan12.runtimeDescriptorArray = VK_TRUE;
+        vulkan12.timelineSemaphore = VK_TRUE;
+        let mut vulkan11: sys::VkPhysicalDeviceVulkan11Features = mem::zeroed();
+        vulkan11.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_1_FEATURES;
+        vulkan11.pNext = &mut vulkan12 as *mut _ as *mut _;
+        vulkan11.multiview = VK_TRUE;
+        vulkan11.shaderDrawParameters = VK_TRUE;
+        let mut features2: sys::VkPhysicalDeviceFeatures2 = mem::zeroed();
+        features2.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_FEATURES_2;
+        features2.pNext = &mut vulkan11 as *mut _ as *mut _;
+        features2.features.geometryShader = VK_TRUE;
+        features2.features.samplerAnisotropy = VK_TRUE;
+
+        features::disable_features2(&mut features2, &["geometryShader", "multiview", "shaderInt8", "runtimeDescriptorArray"]);
+
+        assert_eq!(0, features2.features.geometryShader);
+        assert_eq!(VK_TRUE, features2.features.samplerAnisotropy);
+        assert_eq!(0, vulkan11.multiview);
+        assert_eq!(VK_TRUE, vulkan11.shaderDrawParameters);
+        assert_eq!(0, vulkan12.shaderInt8);
+        assert_eq!(VK_TRUE, vulkan12.timelineSemaphore);
+        // Reported in two structs, and disabled in both
+        assert_eq!(0, vulkan12.runtimeDescriptorArray);
+        assert_eq!(0, descriptor_indexing.runtimeDescriptorArray);
+        assert_eq!(VK_TRUE, descriptor_indexing.descriptorBindingPartiallyBound);
+        // The chain itself is left alone
+        assert_eq!(&mut vulkan11 as *mut _ as *mut _, features2.pNext);
+        assert_eq!(ptr::null_mut(), descriptor_indexing.pNext);
+    }
+}
@@ -0,0 +1,78 @@
+use std::mem;
+use vulkan_device_filter_layer::memory::{
+    self,
+    MemoryBudget,
+    Reservation,
+};
+use vulkan_sys as sys;
+
+const GIB: u64 = 1 << 30;
+
+/// An 8GiB device-local heap and a 16GiB host heap, with one memory type on each
+fn memory_properties() -> sys::VkPhysicalDeviceMemoryProperties {
+    let mut properties: sys::VkPhysicalDeviceMemoryProperties = unsafe { mem::zeroed() };
+    properties.memoryHeapCount = 2;
+    properties.memoryHeaps[0].size = 8 * GIB;
+    properties.memoryHeaps[0].flags = sys::VkMemoryHeapFlagBits_VK_MEMORY_HEAP_DEVICE_LOCAL_BIT as _;
+    properties.memoryHeaps[1].size = 16 * GIB;
+    properties.memoryTypeCount = 2;
+    properties.memoryTypes[0].heapIndex = 0;
+    properties.memoryTypes[1].heapIndex = 1;
+    properties
+}
+
+fn device_memory(handle: usize) -> sys::VkDeviceMemory {
+    handle as sys::VkDeviceMemory
+}
+
+#[test]
+fn budget_reserve_and_free() {
+    let mut budget = MemoryBudget::new(&memory_properties(), 4 * GIB);
+    assert_eq!(Reservation::Reserved(0), budget.reserve(0, 3 * GIB));
+    budget.track(device_memory(0x10), 0, 3 * GIB);
+    assert_eq!(Reservation::OverBudget, budget.reserve(0, 2 * GIB));
+    assert_eq!(Reservation::Reserved(0), budget.reserve(0, GIB));
+    budget.track(device_memory(0x20), 0, GIB);
+    assert_eq!(Reservation::OverBudget, budget.reserve(0, 1));
+
+    // Freeing returns the allocation's size to the heap
+    budget.free(device_memory(0x10));
+    assert_eq!(Reservation::Reserved(0), budget.reserve(0, 3 * GIB));
+    // Unknown allocations are ignored
+    budget.free(device_memory(0x30));
+    assert_eq!(Reservation::OverBudget, budget.reserve(0, 1));
+}
+
+#[test]
+fn budget_unreserve_failed_allocations() {
+    let mut budget = MemoryBudget::new(&memory_properties(), 4 * GIB);
+    assert_eq!(Reservation::Reserved(0), budget.reserve(0, 4 * GIB));
+    budget.unreserve(0, 4 * GIB);
+    assert_eq!(Reservation::Reserved(0), budget.reserve(0, 4 * GIB));
+}
+
+#[test]
+fn budget_ignores_host_heaps() {
+    let mut budget = MemoryBudget::new(&memory_properties(), 4 * GIB);
+    assert_eq!(Reservation::Untracked, budget.reserve(1, 12 * GIB));
+    assert_eq!(Reservation::Untracked, budget.reserve(7, GIB));
+    // A limit above the heap size is the heap size
+    let mut budget = MemoryBudget::new(&memory_properties(), 32 * GIB);
+    assert_eq!(Reservation::OverBudget, budget.reserve(0, 9 * GIB));
+}
+
+#[test]
+fn clamp_heaps_and_budget() {
+    let mut properties = memory_properties();
+    memory::clamp_heaps(&mut properties, 4 * GIB);
+    assert_eq!(4 * GIB, properties.memoryHeaps[0].size);
+    assert_eq!(16 * GIB, properties.memoryHeaps[1].size);
+
+    let mut budget: sys::VkPhysicalDeviceMemoryBudgetPropertiesEXT = unsafe { mem::zeroed() };
+    budget.heapBudget[0] = 7 * GIB;
+    budget.heapUsage[0] = GIB;
+    budget.heapBudget[1] = 14 * GIB;
+    memory::clamp_budget(&mut budget, &properties);
+    assert_eq!(3 * GIB, budget.heapBudget[0]);
+    assert_eq!(14 * GIB, budget.heapBudget[1]);
+}
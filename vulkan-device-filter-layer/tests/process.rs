@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+};
+use vulkan_device_filter_layer::process::{
+    self,
+    SessionType,
+};
+
+mod common;
+
+use common::FakeRoot;
+
+fn session_type(vars: &[(&str, &str)]) -> SessionType {
+    let vars: HashMap<String, String> = vars.iter()
+        .map(|&(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    process::session_type_from(|name| vars.get(name).cloned())
+}
+
+#[test]
+fn session_type_detection() {
+    assert_eq!(SessionType::Wayland, session_type(&[("XDG_SESSION_TYPE", "wayland"), ("DISPLAY", ":0")]));
+    assert_eq!(SessionType::X11, session_type(&[("XDG_SESSION_TYPE", "x11")]));
+    assert_eq!(SessionType::Wayland, session_type(&[("WAYLAND_DISPLAY", "wayland-0"), ("DISPLAY", ":0")]));
+    assert_eq!(SessionType::X11, session_type(&[("XDG_SESSION_TYPE", "unspecified"), ("DISPLAY", ":0")]));
+    assert_eq!(SessionType::Tty, session_type(&[]));
+    assert_eq!(SessionType::Remote, session_type(&[("SSH_CONNECTION", "10.0.0.2 51234 10.0.0.1 22"), ("DISPLAY", "localhost:10.0")]));
+    assert_eq!(SessionType::Remote, session_type(&[("XRDP_SESSION", "1"), ("XDG_SESSION_TYPE", "x11"), ("DISPLAY", ":10")]));
+    assert_eq!(SessionType::Remote, session_type(&[("VNCDESKTOP", "render-07:1 (alice)"), ("DISPLAY", ":1")]));
+    assert_eq!(SessionType::X11, session_type(&[("XRDP_SESSION", ""), ("DISPLAY", ":0")]));
+}
+
+#[test]
+fn credentials_lookup() {
+    // Only look up our own user, since the password database could hold anything else
+    assert!(process::user_name(process::uid()).is_some());
+    assert!(!process::gids().is_empty());
+}
+
+#[test]
+fn flatpak_app_id() {
+    let root = FakeRoot::new("flatpak");
+    assert_eq!(None, process::flatpak_app_id(&root.0));
+    root.write(".flatpak-info", "[Application]\nruntime=runtime/org.freedesktop.Platform/x86_64/22.08\nname=org.blender.Blender\n\n[Instance]\nname=instance\n");
+    assert_eq!(Some("org.blender.Blender".to_string()), process::flatpak_app_id(&root.0));
+}
+
+#[test]
+fn cgroups_and_snaps() {
+    let root = FakeRoot::new("cgroups");
+    assert!(process::cgroups(&root.0).is_empty());
+    root.write("proc/self/cgroup", "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app-steam.scope\n");
+    assert_eq!(vec!["/user.slice/user-1000.slice/user@1000.service/app.slice/app-steam.scope".to_string()], process::cgroups(&root.0));
+    assert_eq!(None, process::snap_name(&root.0));
+    root.write("proc/self/cgroup", "12:pids:/user.slice\n0::/user.slice/user-1000.slice/user@1000.service/app.slice/snap.firefox.firefox-2c4b.scope\n");
+    assert_eq!(2, process::cgroups(&root.0).len());
+    assert_eq!(Some("firefox".to_string()), process::snap_name(&root.0));
+}
+
+#[test]
+fn ancestors_walk_parents() {
+    let root = FakeRoot::new("ancestors");
+    root.write("proc/self/stat", "300 (wine64-preload) S 200 300 100 0 -1 4194304");
+    root.write("proc/200/stat", "200 (Main (thread)) S 100 200 100 0 -1 4194304");
+    root.write("proc/100/stat", "100 (lutris) S 1 100 100 0 -1 4194304");
+    root.write("proc/1/stat", "1 (systemd) S 0 1 1 0 -1 4194560");
+    root.symlink("/usr/bin/python3.11", "proc/100/exe");
+
+    let ancestors = process::ancestors(&root.0, None);
+    let names: Vec<&str> = ancestors.iter().map(|ancestor| ancestor.comm.as_str()).collect();
+    assert_eq!(vec!["Main (thread)", "lutris", "systemd"], names);
+    assert_eq!(None, ancestors[0].exe);
+    assert_eq!(Some(PathBuf::from("/usr/bin/python3.11")), ancestors[1].exe);
+
+    assert_eq!(1, process::ancestors(&root.0, Some(1)).len());
+    assert!(process::ancestors(&root.0, Some(0)).is_empty());
+}
+
+#[test]
+fn ancestors_stop_on_cycles_and_missing_processes() {
+    let root = FakeRoot::new("ancestor-cycle");
+    root.write("proc/self/stat", "30 (game) S 20 30 10 0 -1 0");
+    root.write("proc/20/stat", "20 (a) S 10 20 10 0 -1 0");
+    root.write("proc/10/stat", "10 (b) S 20 10 10 0 -1 0");
+    assert_eq!(2, process::ancestors(&root.0, None).len());
+
+    root.write("proc/10/stat", "10 (b) S 5 10 10 0 -1 0");
+    assert_eq!(2, process::ancestors(&root.0, None).len());
+}
+
+#[test]
+fn host_names() {
+    let root = FakeRoot::new("hostname");
+    assert_eq!(None, process::configured_host_name(&root.0));
+    root.write("etc/hostname", "# managed by ansible\nrender-07\n");
+    assert_eq!(Some("render-07".to_string()), process::configured_host_name(&root.0));
+}
+
+#[test]
+fn files_exist() {
+    let root = FakeRoot::new("file-exists");
+    root.write("MyGame.uproject", "{}");
+    root.write("Config/DefaultEngine.ini", "");
+    assert!(process::file_exists(&root.0, "MyGame.uproject"));
+    assert!(process::file_exists(&root.0, "*.uproject"));
+    assert!(process::file_exists(&root.0, "Config/Default?ngine.ini"));
+    assert!(process::file_exists(&root.0, "Config"));
+    assert!(process::file_exists(&root.0, root.0.join("Config").to_str().unwrap()));
+    assert!(!process::file_exists(&root.0, "project.godot"));
+    assert!(!process::file_exists(&root.0, "*.godot"));
+    assert!(!process::file_exists(&root.0, "Missing/*.ini"));
+}
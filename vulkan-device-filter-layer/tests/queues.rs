@@ -0,0 +1,128 @@
+use std::mem;
+use vulkan_device_filter_layer::queues::{
+    self,
+    QueueRule,
+};
+use vulkan_sys as sys;
+
+const GRAPHICS: sys::VkQueueFlags = sys::VkQueueFlagBits_VK_QUEUE_GRAPHICS_BIT;
+const COMPUTE: sys::VkQueueFlags = sys::VkQueueFlagBits_VK_QUEUE_COMPUTE_BIT;
+const TRANSFER: sys::VkQueueFlags = sys::VkQueueFlagBits_VK_QUEUE_TRANSFER_BIT;
+
+fn rules(yaml: &str) -> Vec<QueueRule> {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+fn family(flags: sys::VkQueueFlags, count: u32) -> sys::VkQueueFamilyProperties {
+    let mut properties: sys::VkQueueFamilyProperties = unsafe { mem::zeroed() };
+    properties.queueFlags = flags;
+    properties.queueCount = count;
+    properties
+}
+
+/// A graphics family, a compute family and a dedicated transfer family, like most discrete GPUs
+fn families() -> Vec<sys::VkQueueFamilyProperties> {
+    vec![
+        family(GRAPHICS | COMPUTE | TRANSFER, 1),
+        family(COMPUTE | TRANSFER, 4),
+        family(TRANSFER, 2),
+    ]
+}
+
+fn apply(rules: &[QueueRule]) -> Vec<(sys::VkQueueFlags, u32)> {
+    families().into_iter()
+        .map(|mut properties| {
+            queues::apply_queue_rules(rules, &mut properties);
+            (properties.queueFlags, properties.queueCount)
+        })
+        .collect()
+}
+
+fn queue_create_info(family_index: u32, count: u32) -> sys::VkDeviceQueueCreateInfo {
+    let mut info: sys::VkDeviceQueueCreateInfo = unsafe { mem::zeroed() };
+    info.sType = sys::VkStructureType_VK_STRUCTURE_TYPE_DEVICE_QUEUE_CREATE_INFO;
+    info.queueFamilyIndex = family_index;
+    info.queueCount = count;
+    info
+}
+
+#[test]
+fn rules_match_on_flags() {
+    let hide_transfer_only = rules("
+- with_flags: [transfer]
+  without_flags: [graphics, compute]
+  hide: true
+");
+    assert_eq!(vec![(GRAPHICS | COMPUTE | TRANSFER, 1), (COMPUTE | TRANSFER, 4), (0, 2)], apply(&hide_transfer_only));
+
+    let hide_compute = rules("
+- with_flags: [compute]
+  hide: true
+");
+    assert_eq!(vec![(0, 1), (0, 4), (TRANSFER, 2)], apply(&hide_compute));
+
+    let hide_all = rules("
+- hide: true
+");
+    assert!(apply(&hide_all).iter().all(|&(flags, _)| flags == 0));
+}
+
+#[test]
+fn hidden_families_keep_their_queue_count() {
+    let hide_compute = rules("
+- with_flags: [compute]
+  without_flags: [graphics]
+  hide: true
+");
+    let mut properties = family(COMPUTE | TRANSFER, 4);
+    queues::apply_queue_rules(&hide_compute, &mut properties);
+    assert!(queues::is_hidden(&properties));
+    assert_eq!(4, properties.queueCount);
+}
+
+#[test]
+fn max_count_caps_queues() {
+    let cap_compute = rules("
+- with_flags: [compute]
+  max_count: 2
+");
+    assert_eq!(vec![(GRAPHICS | COMPUTE | TRANSFER, 1), (COMPUTE | TRANSFER, 2), (TRANSFER, 2)], apply(&cap_compute));
+
+    // Rules are applied to the real properties, so hiding one flag doesn't stop other rules
+    // from matching
+    let hide_and_cap = rules("
+- with_flags: [transfer]
+  hide: true
+- with_flags: [transfer]
+  max_count: 1
+");
+    assert_eq!(vec![(0, 1), (0, 1), (0, 1)], apply(&hide_and_cap));
+}
+
+#[test]
+fn zero_max_count_is_rejected() {
+    assert!(serde_yaml::from_str::<Vec<QueueRule>>("[{ max_count: 0 }]").is_err());
+    assert!(serde_yaml::from_str::<Vec<QueueRule>>("[{ max_count: 1 }]").is_ok());
+}
+
+#[test]
+fn queue_creation_checks() {
+    let rules = rules("
+- with_flags: [transfer]
+  without_flags: [graphics, compute]
+  hide: true
+- with_flags: [compute]
+  without_flags: [graphics]
+  max_count: 2
+");
+    let families = families();
+    let allowed = |infos: &[sys::VkDeviceQueueCreateInfo]| queues::is_queue_creation_allowed(&rules, &families, infos);
+    assert!(allowed(&[]));
+    assert!(allowed(&[queue_create_info(0, 1), queue_create_info(1, 2)]));
+    // More queues than were reported
+    assert!(!allowed(&[queue_create_info(1, 3)]));
+    // Any queue from a hidden family
+    assert!(!allowed(&[queue_create_info(0, 1), queue_create_info(2, 1)]));
+    // Families we know nothing about are left for the driver to reject
+    assert!(allowed(&[queue_create_info(3, 1)]));
+}
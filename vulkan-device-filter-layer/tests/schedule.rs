@@ -0,0 +1,58 @@
+use vulkan_device_filter_layer::schedule::{
+    self,
+    LocalTime,
+    TimeRange,
+    Weekday,
+};
+
+fn at(weekday: Weekday, hour: u32, minute: u32) -> LocalTime {
+    LocalTime {
+        weekday: weekday,
+        minute: hour * 60 + minute,
+    }
+}
+
+fn is_scheduled(days: &[Weekday], hours: &[&str], time: LocalTime) -> bool {
+    let hours: Vec<TimeRange> = hours.iter().map(|range| range.parse().unwrap()).collect();
+    schedule::is_scheduled(Some(days), Some(&hours), time)
+}
+
+#[test]
+fn time_range_parse() {
+    assert_eq!("09:00-17:30", format!("{}", "9:00-17:30".parse::<TimeRange>().unwrap()));
+    assert_eq!("22:00-24:00", format!("{}", "22:00-24:00".parse::<TimeRange>().unwrap()));
+    assert!("09:00".parse::<TimeRange>().is_err());
+    assert!("09:60-10:00".parse::<TimeRange>().is_err());
+    assert!("25:00-10:00".parse::<TimeRange>().is_err());
+}
+
+#[test]
+fn office_hours() {
+    let weekdays = [Weekday::Monday, Weekday::Tuesday, Weekday::Wednesday, Weekday::Thursday, Weekday::Friday];
+    assert!(is_scheduled(&weekdays, &["09:00-17:30"], at(Weekday::Monday, 9, 0)));
+    assert!(is_scheduled(&weekdays, &["09:00-17:30"], at(Weekday::Friday, 17, 29)));
+    assert!(!is_scheduled(&weekdays, &["09:00-17:30"], at(Weekday::Friday, 17, 30)));
+    assert!(!is_scheduled(&weekdays, &["09:00-17:30"], at(Weekday::Saturday, 12, 0)));
+    assert!(is_scheduled(&weekdays, &["07:00-08:00", "09:00-17:30"], at(Weekday::Tuesday, 7, 15)));
+}
+
+#[test]
+fn overnight_ranges_belong_to_their_start_day() {
+    let days = [Weekday::Friday];
+    assert!(is_scheduled(&days, &["22:00-06:00"], at(Weekday::Friday, 23, 0)));
+    assert!(is_scheduled(&days, &["22:00-06:00"], at(Weekday::Saturday, 5, 59)));
+    assert!(!is_scheduled(&days, &["22:00-06:00"], at(Weekday::Friday, 5, 0)));
+    assert!(!is_scheduled(&days, &["22:00-06:00"], at(Weekday::Saturday, 22, 0)));
+    assert!(is_scheduled(&[Weekday::Saturday], &["22:00-06:00"], at(Weekday::Sunday, 1, 0)));
+    assert!(is_scheduled(&[Weekday::Sunday], &["20:00-20:00"], at(Weekday::Monday, 19, 0)));
+}
+
+#[test]
+fn missing_lists_allow_anything() {
+    let sunday_noon = at(Weekday::Sunday, 12, 0);
+    assert!(schedule::is_scheduled(None, None, sunday_noon));
+    assert!(schedule::is_scheduled(Some(&[Weekday::Sunday]), None, sunday_noon));
+    assert!(!schedule::is_scheduled(Some(&[Weekday::Monday]), None, sunday_noon));
+    let night: Vec<TimeRange> = vec!["00:00-06:00".parse().unwrap()];
+    assert!(!schedule::is_scheduled(None, Some(&night), sunday_noon));
+}
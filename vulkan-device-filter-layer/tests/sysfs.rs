@@ -0,0 +1,126 @@
+use vulkan_device_filter_layer::sysfs::{
+    self,
+    DeviceNumber,
+    GpuLoad,
+    PowerSource,
+};
+
+mod common;
+
+use common::FakeRoot;
+
+#[test]
+fn device_number_parse() {
+    assert_eq!(Ok(DeviceNumber { major: 226, minor: 128 }), "226:128\n".parse());
+    assert!("226".parse::<DeviceNumber>().is_err());
+    assert!("a:b".parse::<DeviceNumber>().is_err());
+}
+
+#[test]
+fn drm_cards_from_fake_tree() {
+    let sysfs = FakeRoot::new("drm-cards");
+    // Integrated GPU driving the laptop panel
+    sysfs.write("class/drm/card0/dev", "226:0\n");
+    sysfs.write("class/drm/card0/device/boot_vga", "1\n");
+    sysfs.write("class/drm/card0/device/drm/card0/dev", "226:0\n");
+    sysfs.write("class/drm/card0/device/drm/renderD128/dev", "226:128\n");
+    sysfs.write("class/drm/card0-eDP-1/status", "connected\n");
+    // Discrete GPU with nothing plugged in
+    sysfs.write("class/drm/card1/dev", "226:1\n");
+    sysfs.write("class/drm/card1/device/boot_vga", "0\n");
+    sysfs.write("class/drm/card1/device/drm/card1/dev", "226:1\n");
+    sysfs.write("class/drm/card1/device/drm/renderD129/dev", "226:129\n");
+    sysfs.write("class/drm/card1-HDMI-A-1/status", "disconnected\n");
+    sysfs.write("class/drm/renderD128/dev", "226:128\n");
+    sysfs.write("class/drm/version", "drm 1.1.0 20060810\n");
+
+    let cards = sysfs::drm_cards(&sysfs.0).unwrap();
+    assert_eq!(2, cards.len());
+    assert_eq!("card0", &cards[0].name);
+    assert!(cards[0].boot_vga);
+    assert!(cards[0].connected);
+    assert!(cards[0].has_node(DeviceNumber { major: 226, minor: 128 }));
+    assert_eq!("card1", &cards[1].name);
+    assert!(!cards[1].boot_vga);
+    assert!(!cards[1].connected);
+    assert!(cards[1].has_node(DeviceNumber { major: 226, minor: 1 }));
+    assert!(!cards[1].has_node(DeviceNumber { major: 226, minor: 128 }));
+}
+
+#[test]
+fn drm_cards_missing_root() {
+    let sysfs = FakeRoot::new("drm-missing");
+    assert!(sysfs::drm_cards(&sysfs.0).is_err());
+}
+
+#[test]
+fn power_source_on_ac() {
+    let sysfs = FakeRoot::new("power-ac");
+    sysfs.write("class/power_supply/AC/type", "Mains\n");
+    sysfs.write("class/power_supply/AC/online", "1\n");
+    sysfs.write("class/power_supply/BAT0/type", "Battery\n");
+    sysfs.write("class/power_supply/BAT0/scope", "System\n");
+    assert_eq!(PowerSource::Ac, sysfs::power_source(&sysfs.0));
+}
+
+#[test]
+fn power_source_on_battery() {
+    let sysfs = FakeRoot::new("power-battery");
+    sysfs.write("class/power_supply/AC/type", "Mains\n");
+    sysfs.write("class/power_supply/AC/online", "0\n");
+    sysfs.write("class/power_supply/BAT0/type", "Battery\n");
+    assert_eq!(PowerSource::Battery, sysfs::power_source(&sysfs.0));
+}
+
+#[test]
+fn power_source_ignores_peripheral_batteries() {
+    let sysfs = FakeRoot::new("power-peripheral");
+    sysfs.write("class/power_supply/hidpp_battery_0/type", "Battery\n");
+    sysfs.write("class/power_supply/hidpp_battery_0/scope", "Device\n");
+    assert_eq!(PowerSource::Ac, sysfs::power_source(&sysfs.0));
+
+    let empty = FakeRoot::new("power-none");
+    assert_eq!(PowerSource::Ac, sysfs::power_source(&empty.0));
+}
+
+#[test]
+fn power_source_ignores_offline_supplies_without_battery() {
+    let sysfs = FakeRoot::new("power-ucsi");
+    sysfs.write("class/power_supply/ucsi-source-psy-USBC000:001/type", "USB\n");
+    sysfs.write("class/power_supply/ucsi-source-psy-USBC000:001/online", "0\n");
+    assert_eq!(PowerSource::Ac, sysfs::power_source(&sysfs.0));
+}
+
+#[test]
+fn external_pci_devices() {
+    let sysfs = FakeRoot::new("external");
+    let root_port = "devices/pci0000:00/0000:00:1c.4";
+    // dGPU straight on the root complex
+    sysfs.write("devices/pci0000:00/0000:01:00.0/removable", "fixed\n");
+    sysfs.symlink("../../../devices/pci0000:00/0000:01:00.0", "bus/pci/devices/0000:01:00.0");
+    // eGPU behind an external-facing port
+    sysfs.write(format!("{}/0000:05:00.0/removable", root_port), "removable\n");
+    sysfs.write(format!("{}/0000:05:00.0/0000:06:01.0/0000:08:00.0/removable", root_port), "fixed\n");
+    sysfs.symlink(format!("../../../{}/0000:05:00.0/0000:06:01.0/0000:08:00.0", root_port), "bus/pci/devices/0000:08:00.0");
+    // eGPU behind a port the firmware marked as external-facing, on a kernel without `removable`
+    sysfs.write("devices/pci0000:00/0000:00:1d.0/external_facing", "1\n");
+    sysfs.write("devices/pci0000:00/0000:00:1d.0/0000:0a:00.0/0000:0b:00.0/vendor", "0x1002\n");
+    sysfs.symlink("../../../devices/pci0000:00/0000:00:1d.0", "bus/pci/devices/0000:00:1d.0");
+    sysfs.symlink("../../../devices/pci0000:00/0000:00:1d.0/0000:0a:00.0/0000:0b:00.0", "bus/pci/devices/0000:0b:00.0");
+
+    assert!(!sysfs::is_external_pci_device(&sysfs.0, "0000:01:00.0"));
+    assert!(sysfs::is_external_pci_device(&sysfs.0, "0000:08:00.0"));
+    assert!(sysfs::is_external_pci_device(&sysfs.0, "0000:0b:00.0"));
+    assert!(!sysfs::is_external_pci_device(&sysfs.0, "0000:00:1d.0"));
+    assert!(!sysfs::is_external_pci_device(&sysfs.0, "0000:02:00.0"));
+}
+
+#[test]
+fn gpu_load_counters() {
+    let sysfs = FakeRoot::new("gpu-load");
+    sysfs.write("bus/pci/devices/0000:03:00.0/gpu_busy_percent", "42\n");
+    sysfs.write("bus/pci/devices/0000:03:00.0/mem_info_vram_used", "1073741824\n");
+    sysfs.write("bus/pci/devices/0000:04:00.0/vendor", "0x10de\n");
+    assert_eq!(GpuLoad { busy_percent: Some(42), vram_used: Some(1 << 30) }, sysfs::gpu_load(&sysfs.0, "0000:03:00.0"));
+    assert_eq!(GpuLoad::default(), sysfs::gpu_load(&sysfs.0, "0000:04:00.0"));
+}
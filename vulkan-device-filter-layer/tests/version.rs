@@ -1,6 +1,7 @@
 use vulkan_device_filter_layer::version::{
     VulkanSemanticVersion,
-    SemanticVersion
+    SemanticVersion,
+    VersionReq,
 };
 
 #[test]
@@ -14,3 +15,56 @@ fn vulkan_semantic_version_roundtrip() {
     assert_eq!(minor, vk_version.minor());
     assert_eq!(patch, vk_version.patch());
 }
+
+#[test]
+fn vulkan_semantic_version_parse() {
+    assert_eq!(Ok(VulkanSemanticVersion::new(1, 2, 0)), "1.2".parse());
+    assert_eq!(Ok(VulkanSemanticVersion::new(1, 1, 130)), "1.1.130".parse());
+    assert_eq!(Ok(VulkanSemanticVersion::new(1, 0, 0)), "1".parse());
+    assert!("1.2.3.4".parse::<VulkanSemanticVersion>().is_err());
+    assert!("1.x".parse::<VulkanSemanticVersion>().is_err());
+    assert!("1.1024".parse::<VulkanSemanticVersion>().is_err());
+}
+
+#[test]
+fn vulkan_semantic_version_ordering() {
+    assert!(VulkanSemanticVersion::new(1, 2, 0) < VulkanSemanticVersion::new(1, 3, 0));
+    assert!(VulkanSemanticVersion::new(1, 2, 200) < VulkanSemanticVersion::new(1, 3, 0));
+    assert!(VulkanSemanticVersion::new(2, 0, 0) > VulkanSemanticVersion::new(1, 1023, 4095));
+    assert_eq!(VulkanSemanticVersion::new(1, 1, 0), VulkanSemanticVersion::new(1, 3, 0).min(VulkanSemanticVersion::new(1, 1, 0)));
+}
+
+#[test]
+fn version_req_ranges() {
+    let v = VulkanSemanticVersion::new;
+    let range: VersionReq = ">=1.2, <1.3".parse().unwrap();
+    assert!(range.matches(v(1, 2, 0)));
+    assert!(range.matches(v(1, 2, 4095)));
+    assert!(!range.matches(v(1, 1, 130)));
+    assert!(!range.matches(v(1, 3, 0)));
+
+    let tilde: VersionReq = "~1.2".parse().unwrap();
+    assert!(tilde.matches(v(1, 2, 4095)));
+    assert!(!tilde.matches(v(1, 3, 0)));
+    let major: VersionReq = "~1".parse().unwrap();
+    assert!(major.matches(v(1, 1023, 0)));
+    assert!(!major.matches(v(2, 0, 0)));
+
+    let greater: VersionReq = "> 1.2".parse().unwrap();
+    assert!(!greater.matches(v(1, 2, 200)));
+    assert!(greater.matches(v(1, 3, 0)));
+    let at_most: VersionReq = "<=1.2".parse().unwrap();
+    assert!(at_most.matches(v(1, 2, 200)));
+    assert!(!at_most.matches(v(1, 3, 0)));
+    let exact: VersionReq = "=1.2.3".parse().unwrap();
+    assert!(exact.matches(v(1, 2, 3)));
+    assert!(!exact.matches(v(1, 2, 4)));
+}
+
+#[test]
+fn version_req_parse_errors() {
+    assert!("1.2".parse::<VersionReq>().is_err());
+    assert!(">=1.x".parse::<VersionReq>().is_err());
+    assert!(">=1.2,".parse::<VersionReq>().is_err());
+    assert!("^1.2".parse::<VersionReq>().is_err());
+}
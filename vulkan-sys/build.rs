@@ -13,6 +13,16 @@ fn main() {
         .whitelist_type("PFN.+")
         .whitelist_type("VkLayerInstanceCreateInfo")
         .whitelist_type("VkLayerDeviceCreateInfo")
+        .whitelist_type("VkDeviceGroupDeviceCreateInfo")
+        .whitelist_type("VkEnumerateInstanceVersionChain")
+        .whitelist_type("VkPhysicalDeviceVulkan11Features")
+        .whitelist_type("VkPhysicalDeviceVulkan12Features")
+        .whitelist_type("VkPhysicalDeviceDescriptorIndexingFeatures")
+        .whitelist_type("VkPhysicalDeviceDriverProperties")
+        .whitelist_type("VkPhysicalDevicePCIBusInfoPropertiesEXT")
+        .whitelist_type("VkPhysicalDeviceDrmPropertiesEXT")
+        .whitelist_type("VkPhysicalDeviceIDProperties")
+        .whitelist_type("VkPhysicalDeviceMemoryBudgetPropertiesEXT")
         .generate()
         .expect("Error generating libobs bindings");
 